@@ -7,11 +7,14 @@ use fdt::Fdt;
 use log::info;
 
 use crate::{
-    arch::{config::KERNEL_ADDR_OFFSET, cpu, mm, process, sbi, system, timer, trap},
+    arch::{
+        config::{KERNEL_ADDR_OFFSET, MAX_HARTS},
+        cpu, mm, process, sbi, timer, trap,
+    },
     loader, logging,
 };
 
-global_asm!(include_str!("entry.asm"));
+global_asm!(include_str!("entry.asm"), MAX_HARTS = const MAX_HARTS);
 
 static FIRST_HART: AtomicBool = AtomicBool::new(true);
 
@@ -63,25 +66,28 @@ pub fn kernel_main(hart_id: usize, device_tree_vaddr: usize) -> ! {
     mm::init();
     trap::init();
     loader::init();
-    process::add_initproc();
+    process::add_initproc().expect("failed to load initproc");
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
     timer::set_next_trigger();
+    trap::enable_kernel_interrupt();
 
     // trigger other harts to start
     trigger_other_harts(hart_id, device_tree_vaddr);
-    loop {
-        system::halt();
-    }
+    process::run_threads()
 }
 
 /// Other harts entry, called from kernel_main.
 fn others_main(hart_id: usize) -> ! {
     cpu::init_local_cpu_context(hart_id);
     mm::activate_kernel_space();
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    timer::set_next_trigger();
+    trap::enable_kernel_interrupt();
     info!("hart: {} is starting", cpu::hart_id());
-    loop {
-        system::halt();
-    }
+    process::run_threads()
 }
 
 /// Clear kernel BSS section mannually.
@@ -1,5 +1,7 @@
 use crate::arch::config::mm::KERNEL_ADDR_OFFSET;
 pub const CLOCK_FREQ: usize = 12500000;
+/// Hart ids are below this, the per-hart contexts and stacks are sized by it.
+pub const MAX_HARTS: usize = 16;
 pub const MEMORY_END: usize = 0x8800_0000 + KERNEL_ADDR_OFFSET;
 
 pub const MMIO: &[(usize, usize)] = &[
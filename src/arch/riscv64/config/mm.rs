@@ -3,8 +3,40 @@ pub const KERNEL_ADDR_OFFSET: usize = 0xffff_ffc0_0000_0000;
 
 
 pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
+pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE * 4;
+/// Kernel stacks of threads live in the last 1 GiB, which is used by nothing else.
+/// Each takes a slot of `KERNEL_STACK_SLOT_SIZE`, the lower half of which is an unmapped guard,
+/// so that `__trap_from_kernel` can tell an overflow from `sp` alone.
+pub const KERNEL_STACK_AREA_BASE: usize = 0xffff_ffff_c000_0000;
+pub const KERNEL_STACK_SLOT_SIZE: usize = KERNEL_STACK_SIZE * 2;
 
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 12;
 
 pub const KERNEL_PGNUM_OFFSET: usize = KERNEL_ADDR_OFFSET >> PAGE_SIZE_BITS;
+
+// user memory layout
+pub const USER_SPACE_END: usize = 0x40_0000_0000;
+
+/// Trap context of thread `tid` lives at `TRAP_CONTEXT_BASE - tid * PAGE_SIZE`
+pub const TRAP_CONTEXT_BASE: usize = USER_SPACE_END - PAGE_SIZE;
+
+/// User stack of thread 0 ends at `USER_STACK_TOP` and grows down on demand, but not below
+/// `USER_STACK_TOP - MAIN_STACK_MAX`. User stack of thread `tid` > 0 ends at
+/// `USER_STACK_TOP - MAIN_STACK_MAX - (tid - 1) * (USER_STACK_SIZE + PAGE_SIZE)`.
+pub const USER_STACK_TOP: usize = 0x3f_0000_0000;
+pub const USER_STACK_SIZE: usize = PAGE_SIZE * 8;
+pub const MAIN_STACK_MAX: usize = 0x4000_0000;
+/// A stack doesn't grow closer than this to the area below it, like `stack_guard_gap` of Linux.
+pub const STACK_GUARD_GAP: usize = PAGE_SIZE * 256;
+/// A stack only grows to a fault this far below the user sp, 64 KiB and 32 words like Linux used
+/// to allow, so that a wild pointer into the space left for the stack isn't taken for a push.
+pub const STACK_GROWTH_BELOW_SP: usize = 0x1_0000 + 32 * 8;
+
+/// mmap places areas top-down from here, the stacks and trap contexts of threads are above it.
+pub const MMAP_BASE: usize = 0x20_0000_0000;
+/// The lowest address mmap may use, like `vm.mmap_min_addr` of Linux.
+pub const MMAP_MIN_ADDR: usize = 0x1_0000;
+
+/// The page of code signal handlers return to, right above the user stack of thread 0.
+pub const SIGRETURN_TRAMPOLINE: usize = USER_STACK_TOP;
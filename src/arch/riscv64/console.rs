@@ -1,18 +1,31 @@
 use core::fmt::{self, Write};
 
-use super::sbi::console_putchar;
+use super::sbi::{console_getchar, console_putchar};
 
 pub fn put_fmt(args: fmt::Arguments) {
     Console.write_fmt(args).unwrap();
 }
 
+/// Put raw bytes to console, which may be any encoding or not text at all.
+pub fn put_bytes(bytes: &[u8]) {
+    for &c in bytes {
+        console_putchar(c as usize);
+    }
+}
+
+/// Get a character from console, return `None` if there is no input yet.
+pub fn get_char() -> Option<u8> {
+    match console_getchar() {
+        usize::MAX => None,
+        c => Some(c as u8),
+    }
+}
+
 struct Console;
 
 impl Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
-        }
+        put_bytes(s.as_bytes());
         Ok(())
     }
 }
@@ -1,7 +1,19 @@
-use core::arch::asm;
+use alloc::sync::Arc;
+use core::{
+    arch::asm,
+    ptr::addr_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use spin::Mutex;
+
+use crate::arch::{
+    config::MAX_HARTS,
+    process::{RunQueue, ThreadControlBlock, context::ThreadContext},
+};
 
 const INIT_CPU_CONTEXT: CpuContext = CpuContext::new();
-static mut CPU_CONTEXTS: [CpuContext; 16] = [INIT_CPU_CONTEXT; 16];
+static mut CPU_CONTEXTS: [CpuContext; MAX_HARTS] = [INIT_CPU_CONTEXT; MAX_HARTS];
 
 /// Represents the data occupied by each CPU.
 /// It's addr is contained in `tp` register of each CPU.
@@ -12,6 +24,15 @@ static mut CPU_CONTEXTS: [CpuContext; 16] = [INIT_CPU_CONTEXT; 16];
 pub struct CpuContext {
     hart_id: usize,
     enable: bool,
+    /// thread running on this hart
+    current: Option<Arc<ThreadControlBlock>>,
+    /// context of the scheduling loop, see `run_threads`
+    idle_thread_context: ThreadContext,
+    /// ready threads, other harts may steal from it
+    run_queue: Mutex<RunQueue>,
+    /// a real-time thread is queued here, which may be more favorable than the current one. It's
+    /// set by other harts as well
+    need_resched: AtomicBool,
     // ... to be added
 }
 
@@ -24,8 +45,44 @@ impl CpuContext {
         Self {
             hart_id: usize::MAX,
             enable: false,
+            current: None,
+            idle_thread_context: ThreadContext::zero_init(),
+            run_queue: Mutex::new(RunQueue::new()),
+            need_resched: AtomicBool::new(false),
         }
     }
+
+    pub fn hart_id(&self) -> usize {
+        self.hart_id
+    }
+
+    pub fn current(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.current.clone()
+    }
+
+    pub fn set_current(&mut self, thread: Arc<ThreadControlBlock>) {
+        self.current = Some(thread);
+    }
+
+    pub fn take_current(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        self.current.take()
+    }
+
+    pub fn idle_thread_context_ptr(&mut self) -> *mut ThreadContext {
+        &mut self.idle_thread_context as *mut ThreadContext
+    }
+
+    pub fn run_queue(&self) -> &Mutex<RunQueue> {
+        &self.run_queue
+    }
+
+    pub fn set_need_resched(&self) {
+        self.need_resched.store(true, Ordering::Release);
+    }
+
+    pub fn take_need_resched(&self) -> bool {
+        self.need_resched.swap(false, Ordering::Acquire)
+    }
 }
 
 pub fn init_local_cpu_context(hart_id: usize) {
@@ -46,6 +103,27 @@ unsafe fn get_mut_cpu_context_by_id(hart_id: usize) -> &'static mut CpuContext {
 }
 
 
+/// Get the `CpuContext` of the current CPU.
+#[inline(always)]
+pub fn local_cpu_context() -> &'static mut CpuContext {
+    let context: *mut CpuContext;
+    unsafe {
+        asm!("mv {}, tp", out(reg) context);
+        &mut *context
+    }
+}
+
+/// Get the `CpuContext` of every started CPU.
+/// Only the fields shared between harts, e.g. `run_queue`, should be touched through them.
+pub fn cpu_contexts() -> impl Iterator<Item = &'static CpuContext> {
+    unsafe { (*addr_of!(CPU_CONTEXTS)).iter().filter(|context| context.enable) }
+}
+
+/// The started harts, a bit for each hart id.
+pub fn online_harts() -> usize {
+    cpu_contexts().fold(0, |harts, context| harts | 1 << context.hart_id)
+}
+
 /// Get the hart id of the current CPU.
 #[inline(always)]
 pub fn hart_id() -> usize {
@@ -26,7 +26,7 @@ _start:
 
     .globl boot_stack_bottom
 boot_stack_bottom:
-    .space 4096 * 16 * 8  # 8 CPUS at most
+    .space 4096 * 16 * {MAX_HARTS}
 
     .globl boot_stack_top
 boot_stack_top:
@@ -135,6 +135,12 @@ impl PhysPageNum {
         let kernel_va = pa2kva(pa).0;
         unsafe { core::slice::from_raw_parts_mut(kernel_va as *mut PageTableEntry, 512) }
     }
+
+    pub fn get_mut<T>(&self) -> &'static mut T {
+        let pa: PhysAddr = (*self).into();
+        let kernel_va = pa2kva(pa).0;
+        unsafe { (kernel_va as *mut T).as_mut().unwrap() }
+    }
 }
 
 impl VirtPageNum {
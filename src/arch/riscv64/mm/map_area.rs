@@ -1,21 +1,33 @@
-use alloc::collections::btree_map::BTreeMap;
-use core::clone;
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
 
 use bitflags::bitflags;
+use spin::Mutex;
 
 use super::{
     address::{PhysPageNum, VirtAddr, VirtPageNum},
     frame::{FrameTracker, frame_alloc},
     paging::{page_table::PageTable, pte::PTEFlags},
 };
-use crate::arch::config::{KERNEL_ADDR_OFFSET, PAGE_SIZE};
+use crate::{
+    arch::config::{KERNEL_PGNUM_OFFSET, PAGE_SIZE},
+    error::{SysError, SysResult},
+};
 
+/// A framed area of user space is populated a page at a time on first touch, see `populate`, so
+/// only the pages it has used are in `data_frames`. Kernel pages are all mapped at once, as the
+/// kernel can't take a fault on them.
 pub struct MapArea {
     pub vpn_range: (VirtPageNum, VirtPageNum),
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// Frames may be shared with the same area of forked address spaces, see `share_cow`.
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
+    /// what the pages are filled with when they are populated, they are zeroed otherwise
+    data: Option<AreaData>,
     map_perm: MapPermission,
     map_type: MapType,
     area_type: AreaType,
+    /// the frames stay shared with forked address spaces rather than copied on write, i.e.
+    /// `MAP_SHARED`
+    shared: Option<SharedFrames>,
 }
 
 impl MapArea {
@@ -31,54 +43,206 @@ impl MapArea {
         Self {
             vpn_range: (start_vpn, end_vpn),
             data_frames: BTreeMap::new(),
+            data: None,
             map_perm,
             map_type,
             area_type,
+            shared: None,
+        }
+    }
+
+    /// Keep sharing the frames with forked address spaces, which has to be set before mapping.
+    pub fn set_shared(&mut self) {
+        self.shared = Some(SharedFrames::new());
+    }
+
+    /// Fill the pages with `data` at `offset` of the start va when they are populated.
+    pub fn set_data(&mut self, data: &'static [u8], offset: usize) {
+        self.data = Some(AreaData { data, offset });
+    }
+
+    /// Map the whole area unless it's populated on demand, nothing is left mapped on failure.
+    pub fn map(&mut self, page_table: &mut PageTable) -> SysResult<()> {
+        if self.is_lazy() {
+            return Ok(());
+        }
+        let (start_vpn, end_vpn) = self.vpn_range;
+        for vpn in start_vpn.0..end_vpn.0 {
+            if let Err(err) = self.map_one(VirtPageNum(vpn), page_table) {
+                (start_vpn.0..vpn).for_each(|vpn| self.unmap_one(VirtPageNum(vpn), page_table));
+                return Err(err);
+            }
         }
+        Ok(())
     }
 
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    pub fn unmap(&mut self, page_table: &mut PageTable) {
         let (start_vpn, end_vpn) = self.vpn_range;
         (start_vpn.0..end_vpn.0).for_each(|vpn| {
-            self.map_one(VirtPageNum(vpn), page_table);
+            self.unmap_one(VirtPageNum(vpn), page_table);
         });
     }
 
-    fn map_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) {
-        let ppn: PhysPageNum;
+    fn map_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> SysResult<()> {
         match self.map_type {
-            MapType::Direct => {
-                ppn = PhysPageNum(vpn.0 - KERNEL_ADDR_OFFSET);
-            },
+            MapType::Direct => page_table.map(vpn, PhysPageNum(vpn.0 - KERNEL_PGNUM_OFFSET), self.pte_flags()),
+            MapType::Framed => self.populate(vpn, page_table),
+        }
+    }
+
+    fn unmap_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) {
+        match self.map_type {
+            MapType::Direct => page_table.unmap(vpn),
+            // a page that's not populated yet isn't mapped, nor is an inaccessible one
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                if self.data_frames.remove(&vpn).is_some()
+                    && page_table.translate(vpn).is_some_and(|pte| pte.is_valid())
+                {
+                    page_table.unmap(vpn);
+                }
             },
         }
-        let flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
-        page_table.map(vpn, ppn, flags);
     }
 
-    /// Data: at the `offset` of the start va.
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8], offset: usize) {
+    fn is_lazy(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
+    }
+
+    /// Map the page at `vpn` to a new frame, filled with its part of the data if there's any.
+    /// The page of a shared area is mapped to the frame of the same page of the other address
+    /// spaces sharing it if one of them has populated it first.
+    pub fn populate(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> SysResult<()> {
         assert_eq!(self.map_type, MapType::Framed);
+        let idx = vpn.0 - self.vpn_range.0.0;
+        let new_frame = || -> SysResult<Arc<FrameTracker>> {
+            let frame = frame_alloc().ok_or(SysError::ENOMEM)?;
+            if let Some(data) = &self.data {
+                data.fill(idx, frame.ppn.bytes_array());
+            }
+            Ok(Arc::new(frame))
+        };
+        let frame = match &self.shared {
+            Some(shared) => shared.get_or_try_insert(idx, new_frame)?,
+            None => new_frame()?,
+        };
+        page_table.map(vpn, frame.ppn, self.pte_flags())?;
+        self.data_frames.insert(vpn, frame);
+        Ok(())
+    }
 
-        let mut start: usize = 0;
-        let mut page_offset: usize = offset;
-        let mut cur_vpn = self.vpn_range_begin();
-        let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE - page_offset)];
-            let dst = &mut page_table.translate(cur_vpn).unwrap().ppn().bytes_array()[offset..offset + src.len()];
-            dst.fill(0);
-            dst.copy_from_slice(src);
-            start += PAGE_SIZE - offset;
-            page_offset = 0;
-            if start >= len {
-                break;
+    /// Share the frames of `another` with this unmapped area. Unless the area is shared, pages are
+    /// made read-only in both page tables with `COW` set, and copied on the first write even if
+    /// the area is made writable later on.
+    pub fn share_cow(
+        &mut self,
+        another: &MapArea,
+        page_table: &mut PageTable,
+        another_page_table: &mut PageTable,
+    ) -> SysResult<()> {
+        assert_eq!(self.map_type, MapType::Framed);
+        let cow = self.shared.is_none();
+        let flags = self.page_flags(cow);
+        for (&vpn, frame) in another.data_frames.iter() {
+            if let Some(flags) = flags {
+                if let Err(err) = page_table.map(vpn, frame.ppn, flags) {
+                    // pages of `another` stay COW, which is still correct
+                    while let Some((vpn, _)) = self.data_frames.pop_first() {
+                        page_table.unmap(vpn);
+                    }
+                    return Err(err);
+                }
+                if cow {
+                    another_page_table.remap(vpn, frame.ppn, flags);
+                }
             }
-            cur_vpn.0 += 1;
+            self.data_frames.insert(vpn, frame.clone());
+        }
+        Ok(())
+    }
+
+    /// Change the permission of the area, the populated pages are remapped with it.
+    pub fn set_perm(&mut self, map_perm: MapPermission, page_table: &mut PageTable) -> SysResult<()> {
+        self.map_perm = map_perm;
+        for (&vpn, frame) in self.data_frames.iter() {
+            // a frame still shared with a forked address space is copied on write
+            let cow = self.shared.is_none() && Arc::strong_count(frame) > 1;
+            let mapped = page_table.translate(vpn).is_some_and(|pte| pte.is_valid());
+            match (self.page_flags(cow), mapped) {
+                (Some(flags), true) => page_table.remap(vpn, frame.ppn, flags),
+                (Some(flags), false) => page_table.map(vpn, frame.ppn, flags)?,
+                (None, true) => page_table.unmap(vpn),
+                (None, false) => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the area end at `end_vpn` instead, which is only done to one populated on demand as
+    /// nothing has to be mapped.
+    pub fn grow(&mut self, end_vpn: VirtPageNum) {
+        assert!(self.is_lazy() && end_vpn >= self.vpn_range.1);
+        self.vpn_range.1 = end_vpn;
+    }
+
+    /// Make the area start at `start_vpn` instead, like `grow` but downwards, which the data
+    /// would be shifted by, so there must be none.
+    pub fn grow_down(&mut self, start_vpn: VirtPageNum) {
+        assert!(self.is_lazy() && self.data.is_none() && start_vpn <= self.vpn_range.0);
+        self.vpn_range.0 = start_vpn;
+    }
+
+    /// Split the area before `vpn`, which is inside it, and return the part from `vpn` on.
+    pub fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        assert!(self.vpn_range.0 < vpn && vpn < self.vpn_range.1);
+        let skipped = (vpn.0 - self.vpn_range.0.0) * PAGE_SIZE;
+        let tail = Self {
+            vpn_range: (vpn, self.vpn_range.1),
+            data_frames: self.data_frames.split_off(&vpn),
+            data: self.data.and_then(|data| data.skip(skipped)),
+            map_perm: self.map_perm,
+            map_type: self.map_type,
+            area_type: self.area_type,
+            shared: self
+                .shared
+                .as_ref()
+                .map(|shared| shared.skip(vpn.0 - self.vpn_range.0.0)),
+        };
+        self.vpn_range.1 = vpn;
+        tail
+    }
+
+    /// Give the page a frame of its own on a write, which is copied only if it's still shared.
+    pub fn copy_on_write(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> SysResult<()> {
+        let frame = self.data_frames.get(&vpn).unwrap();
+        // nobody else can get a new reference, the memory set of its only owner is locked
+        if Arc::strong_count(frame) == 1 {
+            page_table.remap(vpn, frame.ppn, self.pte_flags());
+            return Ok(());
+        }
+        let new_frame = frame_alloc().ok_or(SysError::ENOMEM)?;
+        new_frame.ppn.bytes_array().copy_from_slice(frame.ppn.bytes_array());
+        page_table.remap(vpn, new_frame.ppn, self.pte_flags());
+        self.data_frames.insert(vpn, Arc::new(new_frame));
+        Ok(())
+    }
+
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits()).unwrap()
+    }
+
+    /// Flags of a populated page, read-only with `COW` set if it's `cow`. `None` if it can't be
+    /// accessed at all, which is left unmapped while keeping its frame, as an entry without `R`,
+    /// `W` and `X` points to the next level.
+    fn page_flags(&self, cow: bool) -> Option<PTEFlags> {
+        if !self
+            .map_perm
+            .intersects(MapPermission::R | MapPermission::W | MapPermission::X)
+        {
+            return None;
+        }
+        match cow {
+            true => Some((self.pte_flags() - PTEFlags::W) | PTEFlags::COW),
+            false => Some(self.pte_flags()),
         }
     }
 
@@ -86,12 +250,30 @@ impl MapArea {
         Self {
             vpn_range: another.vpn_range,
             data_frames: BTreeMap::new(),
+            data: another.data,
             map_perm: another.map_perm,
             map_type: another.map_type,
             area_type: another.area_type,
+            shared: another.shared.clone(),
         }
     }
 
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.0 <= vpn && vpn < self.vpn_range.1
+    }
+
+    pub fn map_perm(&self) -> MapPermission {
+        self.map_perm
+    }
+
+    pub fn map_type(&self) -> MapType {
+        self.map_type
+    }
+
+    pub fn area_type(&self) -> AreaType {
+        self.area_type
+    }
+
     pub fn vpn_range_begin(&self) -> VirtPageNum {
         self.vpn_range.0
     }
@@ -101,6 +283,86 @@ impl MapArea {
     }
 }
 
+/// The frames of a shared area by page, which every address space sharing it maps, populated by
+/// whichever touches a page first. It's only split by `split_off`, so a page is at the same index
+/// for all of them. Like the pages of a shmem file on Linux, the frames stay until no part of the
+/// area is left anywhere, as an address space that hasn't touched a page yet may still do.
+#[derive(Clone)]
+struct SharedFrames {
+    frames: Arc<Mutex<BTreeMap<usize, Arc<FrameTracker>>>>,
+    /// the index of the first page of the area
+    offset: usize,
+}
+
+impl SharedFrames {
+    fn new() -> Self {
+        Self {
+            frames: Arc::new(Mutex::new(BTreeMap::new())),
+            offset: 0,
+        }
+    }
+
+    /// The frame of the `idx`-th page of the area, a new one from `new_frame` if it's not
+    /// populated yet.
+    fn get_or_try_insert(
+        &self,
+        idx: usize,
+        new_frame: impl FnOnce() -> SysResult<Arc<FrameTracker>>,
+    ) -> SysResult<Arc<FrameTracker>> {
+        let mut frames = self.frames.lock();
+        if let Some(frame) = frames.get(&(self.offset + idx)) {
+            return Ok(frame.clone());
+        }
+        let frame = new_frame()?;
+        frames.insert(self.offset + idx, frame.clone());
+        Ok(frame)
+    }
+
+    /// The frames of the area starting `pages` pages later.
+    fn skip(&self, pages: usize) -> Self {
+        Self {
+            frames: self.frames.clone(),
+            offset: self.offset + pages,
+        }
+    }
+}
+
+/// The initial content of an area, e.g. a segment of an elf: `data` is at `offset` of the start
+/// va, and the rest is zeroed.
+#[derive(Clone, Copy)]
+struct AreaData {
+    data: &'static [u8],
+    offset: usize,
+}
+
+impl AreaData {
+    /// Copy the part of the data in the `idx`-th page of the area into `page`.
+    fn fill(&self, idx: usize, page: &mut [u8]) {
+        let page_start = idx * PAGE_SIZE;
+        let start = self.offset.max(page_start);
+        let end = (self.offset + self.data.len()).min(page_start + PAGE_SIZE);
+        if start < end {
+            page[start - page_start..end - page_start]
+                .copy_from_slice(&self.data[start - self.offset..end - self.offset]);
+        }
+    }
+
+    /// The data of the area starting `len` bytes later, `None` if nothing is left of it.
+    fn skip(&self, len: usize) -> Option<Self> {
+        if len <= self.offset {
+            return Some(Self {
+                data: self.data,
+                offset: self.offset - len,
+            });
+        }
+        let skipped = len - self.offset;
+        (skipped < self.data.len()).then(|| Self {
+            data: &self.data[skipped..],
+            offset: 0,
+        })
+    }
+}
+
 /// kernel area uses direct mapping
 /// user area uses frame mapping
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
@@ -121,6 +383,8 @@ pub enum AreaType {
     Mmap,
     /// For Trap Context
     Trap,
+    /// Code provided by the kernel, i.e. the sigreturn trampoline
+    Trampoline,
     /// Shared memory
     Shm,
     /// Physical frames(for kernel)
@@ -1,19 +1,30 @@
 use alloc::vec::Vec;
-use core::arch::asm;
+use core::{arch::asm, cmp::Reverse, mem};
 
 use log::info;
 use riscv::register::satp::{self, Satp};
 use spin::{Lazy, Mutex};
 use xmas_elf::ElfFile;
 
-use super::{address::VirtAddr, map_area::MapArea, paging::page_table::PageTable};
-use crate::arch::{
-    board::qemu::MEMORY_END,
-    config::PAGE_SIZE,
-    mm::{
-        address::VirtPageNum,
-        map_area::{AreaType, MapPermission, MapType},
+use super::{
+    address::{PhysAddr, VirtAddr},
+    map_area::MapArea,
+    paging::{page_table::PageTable, pte::PageTableEntry},
+};
+use crate::{
+    arch::{
+        board::qemu::MEMORY_END,
+        config::{
+            KERNEL_STACK_AREA_BASE, MAIN_STACK_MAX, MMAP_BASE, MMAP_MIN_ADDR, PAGE_SIZE, SIGRETURN_TRAMPOLINE,
+            STACK_GROWTH_BELOW_SP, STACK_GUARD_GAP, USER_SPACE_END, USER_STACK_TOP,
+        },
+        mm::{
+            address::VirtPageNum,
+            map_area::{AreaType, MapPermission, MapType},
+        },
+        sbi,
     },
+    error::{SysError, SysResult},
 };
 
 pub static KERNEL_SPACE: Lazy<Mutex<MemorySet>> = Lazy::new(|| Mutex::new(MemorySet::new_kernel()));
@@ -25,26 +36,319 @@ pub fn activate_kernel_space() {
 pub struct MemorySet {
     pub page_table: PageTable,
     pub areas: Vec<MapArea>,
+    /// where the heap starts, right above the program with a page between
+    heap_bottom: usize,
+    /// the program break, i.e. the end of the heap
+    brk: usize,
 }
 
 impl MemorySet {
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    pub fn new_bare() -> SysResult<Self> {
+        Ok(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
+        })
+    }
+
+    pub fn token(&self) -> usize {
+        self.page_table.token()
+    }
+
+    /// Assume that no conflicts.
+    pub fn insert_framed_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        area_type: AreaType,
+    ) -> SysResult<()> {
+        self.push(
+            MapArea::new(start_va, end_va, MapType::Framed, permission, area_type),
+            None,
+            0,
+        )
+    }
+
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some(idx) = self.areas.iter().position(|area| area.vpn_range_begin() == start_vpn) {
+            let mut area = self.areas.remove(idx);
+            area.unmap(&mut self.page_table);
+        }
+    }
+
+    /// Remove the pages in `[start_vpn, end_vpn)` from the areas they are in, the areas across the
+    /// ends are split.
+    pub fn remove_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        self.split_at(start_vpn);
+        self.split_at(end_vpn);
+        let (removed, kept) = mem::take(&mut self.areas)
+            .into_iter()
+            .partition(|area| start_vpn <= area.vpn_range_begin() && area.vpn_range_end() <= end_vpn);
+        self.areas = kept;
+        for mut area in removed.into_iter() {
+            area.unmap(&mut self.page_table);
+        }
+    }
+
+    /// Split the area `vpn` is in before it, unless it's the first page already.
+    fn split_at(&mut self, vpn: VirtPageNum) {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.contains(vpn) && area.vpn_range_begin() != vpn)
+        {
+            let tail = area.split_off(vpn);
+            self.areas.push(tail);
+        }
+    }
+
+    /// The areas overlapping `[start_vpn, end_vpn)`.
+    fn areas_in(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> impl Iterator<Item = &MapArea> {
+        self.areas
+            .iter()
+            .filter(move |area| area.vpn_range_begin() < end_vpn && start_vpn < area.vpn_range_end())
+    }
+
+    /// The part of `user_size` in the `len` bytes at `start`.
+    pub fn user_size_in(&self, start: usize, len: usize) -> usize {
+        let (start_vpn, end_vpn) = (VirtAddr(start).floor(), VirtAddr(start + len).ceil());
+        self.areas_in(start_vpn, end_vpn)
+            .filter(|area| area.area_type() != AreaType::Trap)
+            .map(|area| (area.vpn_range_end().min(end_vpn).0 - area.vpn_range_begin().max(start_vpn).0) * PAGE_SIZE)
+            .sum()
+    }
+
+    /// Whether nothing is mapped in the `len` bytes at `start`.
+    pub fn is_free(&self, start: usize, len: usize) -> bool {
+        let (start_vpn, end_vpn) = (VirtAddr(start).floor(), VirtAddr(start + len).ceil());
+        self.areas_in(start_vpn, end_vpn).next().is_none()
+    }
+
+    /// Find `len` free bytes for mmap, at `hint` if it's free, otherwise the highest ones below
+    /// `MMAP_BASE`.
+    pub fn find_free_range(&self, hint: usize, len: usize) -> Option<usize> {
+        let hint = hint & !(PAGE_SIZE - 1);
+        if hint >= MMAP_MIN_ADDR && hint <= MMAP_BASE.saturating_sub(len) && self.is_free(hint, len) {
+            return Some(hint);
+        }
+        let mut ranges: Vec<(usize, usize)> = self
+            .areas
+            .iter()
+            .map(|area| {
+                (
+                    VirtAddr::from(area.vpn_range_begin()).0,
+                    VirtAddr::from(area.vpn_range_end()).0,
+                )
+            })
+            .collect();
+        ranges.sort_unstable_by_key(|&(start, _)| Reverse(start));
+        // the top of the gap below the areas seen so far
+        let mut top = MMAP_BASE;
+        for (start, end) in ranges {
+            if end <= top && top - end >= len {
+                return Some(top - len);
+            }
+            top = top.min(start);
+        }
+        top.checked_sub(len).filter(|&start| start >= MMAP_MIN_ADDR)
+    }
+
+    /// Map `len` bytes of anonymous memory at `start`, which must be free. The pages are shared
+    /// with forked address spaces if `shared`.
+    pub fn mmap(&mut self, start: usize, len: usize, perm: MapPermission, shared: bool) -> SysResult<()> {
+        let mut area = MapArea::new(
+            start.into(),
+            (start + len).into(),
+            MapType::Framed,
+            perm,
+            AreaType::Mmap,
+        );
+        if shared {
+            area.set_shared();
+        }
+        self.push(area, None, 0)
+    }
+
+    /// Unmap the pages in the `len` bytes at `start` of the active address space, whether they
+    /// are mapped or not. `EINVAL` if some are the kernel's, e.g. a trap context.
+    pub fn munmap(&mut self, start: usize, len: usize) -> SysResult<()> {
+        let (start_vpn, end_vpn) = (VirtAddr(start).floor(), VirtAddr(start + len).ceil());
+        if self
+            .areas_in(start_vpn, end_vpn)
+            .any(|area| !area.map_perm().contains(MapPermission::U))
+        {
+            return Err(SysError::EINVAL);
+        }
+        self.remove_range(start_vpn, end_vpn);
+        flush_tlb_all();
+        Ok(())
+    }
+
+    /// Change the permission of the pages in the `len` bytes at `start` of the active address
+    /// space to `perm`. `ENOMEM` if some of them are not mapped by user.
+    pub fn mprotect(&mut self, start: usize, len: usize, perm: MapPermission) -> SysResult<()> {
+        let (start_vpn, end_vpn) = (VirtAddr(start).floor(), VirtAddr(start + len).ceil());
+        let mut mapped = 0;
+        for area in self.areas_in(start_vpn, end_vpn) {
+            if !area.map_perm().contains(MapPermission::U) {
+                return Err(SysError::ENOMEM);
+            }
+            mapped += area.vpn_range_end().0.min(end_vpn.0) - area.vpn_range_begin().0.max(start_vpn.0);
         }
+        if mapped != end_vpn.0 - start_vpn.0 {
+            return Err(SysError::ENOMEM);
+        }
+        self.split_at(start_vpn);
+        self.split_at(end_vpn);
+        let result = self
+            .areas
+            .iter_mut()
+            .filter(|area| start_vpn <= area.vpn_range_begin() && area.vpn_range_end() <= end_vpn)
+            .try_for_each(|area| area.set_perm(perm, &mut self.page_table));
+        flush_tlb_all();
+        result
+    }
+
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    /// Move the program break to `brk`, the heap grows or shrinks by whole pages, which are
+    /// populated on first touch. `ENOMEM` if it's below the bottom of the heap or runs into
+    /// another area.
+    pub fn set_brk(&mut self, brk: usize) -> SysResult<()> {
+        if brk < self.heap_bottom || brk > MMAP_BASE {
+            return Err(SysError::ENOMEM);
+        }
+        let (old_end, new_end) = (VirtAddr(self.brk).ceil(), VirtAddr(brk).ceil());
+        if new_end < old_end {
+            self.remove_range(new_end, old_end);
+            flush_tlb_all();
+        } else if new_end > old_end {
+            if self.areas_in(old_end, new_end).next().is_some() {
+                return Err(SysError::ENOMEM);
+            }
+            let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+            // the heap may have been split by mprotect, only its top is grown
+            match self.areas.iter_mut().find(|area| {
+                area.area_type() == AreaType::Brk
+                    && area.vpn_range_end() == old_end
+                    && area.map_perm().bits() == perm.bits()
+            }) {
+                Some(area) => area.grow(new_end),
+                None => self.push(
+                    MapArea::new(old_end.into(), new_end.into(), MapType::Framed, perm, AreaType::Brk),
+                    None,
+                    0,
+                )?,
+            }
+        }
+        self.brk = brk;
+        Ok(())
+    }
+
+    /// Where the stack ending at `top_vpn` starts, which is `top_vpn` if there's none. The stack
+    /// may have been split by mprotect.
+    pub fn stack_bottom(&self, top_vpn: VirtPageNum) -> VirtPageNum {
+        let mut bottom = top_vpn;
+        while let Some(area) = self
+            .areas
+            .iter()
+            .find(|area| area.area_type() == AreaType::Stack && area.vpn_range_end() == bottom)
+        {
+            bottom = area.vpn_range_begin();
+        }
+        bottom
+    }
+
+    /// Unmap the stack ending at `ustack_top` and the trap context page at `trap_cx_bottom` of an
+    /// exiting thread, on harts running its sibling threads too.
+    pub fn remove_thread_res(&mut self, ustack_top: VirtPageNum, trap_cx_bottom: VirtPageNum) {
+        // the stack may have been split by mprotect, and the one of thread 0 may have grown
+        let ustack_bottom = self.stack_bottom(ustack_top);
+        self.remove_range(ustack_bottom, ustack_top);
+        self.remove_area_with_start_vpn(trap_cx_bottom);
+        flush_tlb_all();
+    }
+
+    /// Grow the stack of thread 0 down to `vpn`, which is below it and within
+    /// `STACK_GROWTH_BELOW_SP` of the user `sp`. It takes no more than `max_size` bytes from
+    /// `USER_STACK_TOP` and `max_growth` more bytes, and keeps `STACK_GUARD_GAP` from the area
+    /// below. `EFAULT` if it can't grow, as for a fault outside of any area.
+    pub fn grow_stack(&mut self, vpn: VirtPageNum, sp: usize, max_size: usize, max_growth: usize) -> SysResult<()> {
+        let top = VirtAddr(USER_STACK_TOP).floor();
+        let bottom = self.stack_bottom(top);
+        let lowest = USER_STACK_TOP - max_size.min(MAIN_STACK_MAX);
+        let below_end = self
+            .areas
+            .iter()
+            .map(MapArea::vpn_range_end)
+            .filter(|&end| end <= vpn)
+            .max()
+            .map_or(0, |end| VirtAddr::from(end).0);
+        let start = VirtAddr::from(vpn).0;
+        if bottom == top
+            || vpn >= bottom
+            || start < lowest
+            || start + PAGE_SIZE + STACK_GROWTH_BELOW_SP <= sp
+            || start - below_end < STACK_GUARD_GAP
+            || VirtAddr::from(bottom).0 - start > max_growth
+        {
+            return Err(SysError::EFAULT);
+        }
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.area_type() == AreaType::Stack && area.vpn_range_begin() == bottom)
+            .unwrap();
+        area.grow_down(vpn);
+        Ok(())
+    }
+
+    /// Unmap and release all areas, the page table goes with the memory set.
+    pub fn recycle_data_pages(&mut self) {
+        for mut area in self.areas.drain(..) {
+            area.unmap(&mut self.page_table);
+        }
+    }
+
+    /// Bytes of address space taken by the user, which is what `RLIMIT_AS` limits. The trap context
+    /// pages are left out, since they are the kernel's.
+    pub fn user_size(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.area_type() != AreaType::Trap)
+            .map(|area| (area.vpn_range_end().0 - area.vpn_range_begin().0) * PAGE_SIZE)
+            .sum()
+    }
+
+    pub fn find_area(&self, vpn: VirtPageNum) -> Option<&MapArea> {
+        self.areas.iter().find(|area| area.contains(vpn))
     }
 
-    pub fn push(&mut self, mut area: MapArea, data: Option<&[u8]>, offset: usize) {
-        area.map(&mut self.page_table);
+    /// Add `area` with `data` at `offset` of its start va, see `MapArea::set_data`.
+    pub fn push(&mut self, mut area: MapArea, data: Option<&'static [u8]>, offset: usize) -> SysResult<()> {
+        // an area populated on demand maps nothing yet to conflict with
+        let (start_vpn, end_vpn) = area.vpn_range;
+        if self
+            .areas
+            .iter()
+            .any(|other| other.vpn_range_begin() < end_vpn && start_vpn < other.vpn_range_end())
+        {
+            return Err(SysError::EEXIST);
+        }
         if let Some(data) = data {
-            area.copy_data(&mut self.page_table, data, offset);
+            area.set_data(data, offset);
         }
+        area.map(&mut self.page_table)?;
         self.areas.push(area);
+        Ok(())
     }
 
     pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().expect("no memory for kernel page table");
         info!("kernel satp: {:#x}", memory_set.page_table.token());
         info!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
         info!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
@@ -55,93 +359,191 @@ impl MemorySet {
         info!("physical memory: [{:#x},{:#x})", ekernel as usize, MEMORY_END);
 
         info!("[kernel]mapping .text section");
-        memory_set.push(
-            MapArea::new(
-                (stext as usize).into(),
-                (etext as usize).into(),
-                MapType::Direct,
-                MapPermission::R | MapPermission::X,
-                AreaType::Elf,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (stext as usize).into(),
+                    (etext as usize).into(),
+                    MapType::Direct,
+                    MapPermission::R | MapPermission::X,
+                    AreaType::Elf,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
 
         info!("[kernel]mapping .rodata section");
-        memory_set.push(
-            MapArea::new(
-                (srodata as usize).into(),
-                (erodata as usize).into(),
-                MapType::Direct,
-                MapPermission::R,
-                AreaType::Elf,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (srodata as usize).into(),
+                    (erodata as usize).into(),
+                    MapType::Direct,
+                    MapPermission::R,
+                    AreaType::Elf,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
 
         info!("[kernel]mapping.data section");
-        memory_set.push(
-            MapArea::new(
-                (sdata as usize).into(),
-                (edata as usize).into(),
-                MapType::Direct,
-                MapPermission::R | MapPermission::W,
-                AreaType::Elf,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (sdata as usize).into(),
+                    (edata as usize).into(),
+                    MapType::Direct,
+                    MapPermission::R | MapPermission::W,
+                    AreaType::Elf,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
 
         info!("[kernel]mapping .stack section");
-        memory_set.push(
-            MapArea::new(
-                (sstack as usize).into(),
-                (estack as usize).into(),
-                MapType::Direct,
-                MapPermission::R | MapPermission::W,
-                AreaType::Elf,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (sstack as usize).into(),
+                    (estack as usize).into(),
+                    MapType::Direct,
+                    MapPermission::R | MapPermission::W,
+                    AreaType::Elf,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
 
         info!("[kernel]mapping.bss section");
-        memory_set.push(
-            MapArea::new(
-                (sbss as usize).into(),
-                (ebss as usize).into(),
-                MapType::Direct,
-                MapPermission::R | MapPermission::W,
-                AreaType::Elf,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (sbss as usize).into(),
+                    (ebss as usize).into(),
+                    MapType::Direct,
+                    MapPermission::R | MapPermission::W,
+                    AreaType::Elf,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
 
         info!("[kernel]mapping physical memory");
-        memory_set.push(
-            MapArea::new(
-                (ekernel as usize).into(),
-                MEMORY_END.into(),
-                MapType::Direct,
-                MapPermission::R | MapPermission::W,
-                AreaType::Physical,
-            ),
-            None,
-            0,
-        );
+        memory_set
+            .push(
+                MapArea::new(
+                    (ekernel as usize).into(),
+                    MEMORY_END.into(),
+                    MapType::Direct,
+                    MapPermission::R | MapPermission::W,
+                    AreaType::Physical,
+                ),
+                None,
+                0,
+            )
+            .unwrap();
+
+        // user page tables only copy the root entries, kernel stacks are mapped later on
+        info!("[kernel]preparing kernel stack area");
+        memory_set
+            .page_table
+            .alloc_tables(VirtAddr::from(KERNEL_STACK_AREA_BASE).floor())
+            .unwrap();
 
         info!("[kernel] new kernel finished");
 
         memory_set
     }
 
-    pub fn new_from_kernel() -> Self {
-        Self {
-            page_table: PageTable::new_from_kernel(),
+    pub fn new_from_kernel() -> SysResult<Self> {
+        Ok(Self {
+            page_table: PageTable::new_from_kernel()?,
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
+        })
+    }
+
+    /// Duplicate the active user address space for a child of the thread whose stack ends at
+    /// `ustack_top` and whose trap context is at `trap_cx_bottom`, framed pages are shared
+    /// copy-on-write. The stacks and trap contexts of the other threads are left out, as the
+    /// child has none of them.
+    pub fn from_existed_user(
+        user_space: &mut Self,
+        ustack_top: VirtPageNum,
+        trap_cx_bottom: VirtPageNum,
+    ) -> SysResult<Self> {
+        let mut memory_set = Self::new_from_kernel()?;
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
+        let ustack_bottom = user_space.stack_bottom(ustack_top);
+        for area in user_space.areas.iter() {
+            let is_other_thread = match area.area_type() {
+                AreaType::Stack => area.vpn_range_begin() < ustack_bottom || area.vpn_range_end() > ustack_top,
+                AreaType::Trap => area.vpn_range_begin() != trap_cx_bottom,
+                _ => false,
+            };
+            if is_other_thread {
+                continue;
+            }
+            let mut new_area = MapArea::from_existed_map_area(area);
+            // the kernel writes trap contexts through their frames, bypassing the page table
+            if area.map_type() == MapType::Framed && area.area_type() != AreaType::Trap {
+                new_area.share_cow(area, &mut memory_set.page_table, &mut user_space.page_table)?;
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            memory_set.push(new_area, None, 0)?;
+            if area.map_type() == MapType::Framed {
+                for vpn in area.vpn_range_begin().0..area.vpn_range_end().0 {
+                    let vpn = VirtPageNum(vpn);
+                    let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.page_table.translate(vpn).unwrap().ppn();
+                    dst_ppn.bytes_array().copy_from_slice(src_ppn.bytes_array());
+                }
+            }
         }
+        // writable pages of the parent are read-only now, on harts running its other threads too
+        flush_tlb_all();
+        Ok(memory_set)
+    }
+
+    /// Resolve a page fault of the active user address space at `va` by an `access`, which is one
+    /// of `R`, `W` and `X`: the page is populated on first touch, or copied on the first write if
+    /// it's copy-on-write. Return `EFAULT` if the access isn't allowed at all.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission) -> SysResult<()> {
+        self.fault_in(va.floor(), access | MapPermission::U)?;
+        // the stale entry may be cached even if another thread has resolved the fault
+        unsafe { asm!("sfence.vma {}", in(reg) va.0) };
+        Ok(())
+    }
+
+    /// Make the page at `vpn` of an area with `access` ready for it and return its entry.
+    fn fault_in(&mut self, vpn: VirtPageNum, access: MapPermission) -> SysResult<PageTableEntry> {
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.contains(vpn))
+            .filter(|area| area.map_perm().contains(access))
+            .ok_or(SysError::EFAULT)?;
+        match self.page_table.translate(vpn).filter(|pte| pte.is_valid()) {
+            None => area.populate(vpn, &mut self.page_table)?,
+            Some(pte) if pte.is_cow() && access.contains(MapPermission::W) => {
+                area.copy_on_write(vpn, &mut self.page_table)?;
+                // other threads of the process would keep reading the old frame through the
+                // entries cached by their harts
+                if self.page_table.translate(vpn).unwrap().ppn() != pte.ppn() {
+                    sbi::remote_sfence_vma(VirtAddr::from(vpn).0, PAGE_SIZE);
+                }
+            },
+            Some(pte) => return Ok(pte),
+        }
+        Ok(self.page_table.translate(vpn).unwrap())
     }
 
     pub fn activate(&self) {
@@ -152,24 +554,37 @@ impl MemorySet {
         }
     }
 
-    fn map_elf(&mut self, elf: &ElfFile, offset: VirtAddr) -> (VirtPageNum, VirtAddr) {
+    /// Return the end of the last segment and the va of the program headers, 0 if they aren't
+    /// loaded.
+    fn map_elf(&mut self, elf: &ElfFile<'static>, offset: VirtAddr) -> SysResult<(VirtPageNum, VirtAddr)> {
         let elf_header = elf.header;
         let ph_count = elf_header.pt2.ph_count();
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
 
         let mut max_end_vpn = offset.floor();
-        // header va is the start va of the first loadable segment
-        let mut header_va = 0;
-        let mut has_found_header_va = false;
+        let mut phdr_va = 0;
 
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                // get segment start va and end va
-                let start_va: VirtAddr = (ph.virtual_addr() as usize + offset.0).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize + offset.0).into();
-                if !has_found_header_va {
-                    header_va = start_va.0;
-                    has_found_header_va = true;
+            let ph = elf.program_header(i).map_err(|_| SysError::ENOEXEC)?;
+            if ph.get_type().map_err(|_| SysError::ENOEXEC)? == xmas_elf::program::Type::Load {
+                // get segment start va and end va, which have to be in the user space
+                if ph.file_size() > ph.mem_size() {
+                    return Err(SysError::ENOEXEC);
+                }
+                let start = (ph.virtual_addr() as usize)
+                    .checked_add(offset.0)
+                    .ok_or(SysError::ENOEXEC)?;
+                let end = start
+                    .checked_add(ph.mem_size() as usize)
+                    .filter(|&end| end <= USER_SPACE_END)
+                    .ok_or(SysError::ENOEXEC)?;
+                let (start_va, end_va) = (VirtAddr(start), VirtAddr(end));
+                let file_end = (ph.offset() as usize)
+                    .checked_add(ph.file_size() as usize)
+                    .ok_or(SysError::ENOEXEC)?;
+                let file_range = ph.offset() as usize..file_end;
+                if phdr_va == 0 && file_range.contains(&ph_offset) {
+                    phdr_va = start_va.0 + ph_offset - file_range.start;
                 }
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
@@ -185,42 +600,100 @@ impl MemorySet {
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm, AreaType::Elf);
                 let data_offset = start_va.page_offset();
                 max_end_vpn = map_area.vpn_range_end();
-                self.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                    data_offset,
-                );
+                let data = elf.input.get(file_range).ok_or(SysError::ENOEXEC)?;
+                self.push(map_area, Some(data), data_offset)?;
             }
         }
-        (max_end_vpn, header_va.into())
+        Ok((max_end_vpn, phdr_va.into()))
+    }
+
+    /// Write `data` at `va` of a writable user area through the frames, so this memory set need
+    /// not be active.
+    pub fn write_bytes(&mut self, va: usize, data: &[u8]) -> SysResult<()> {
+        let mut written = 0;
+        while written < data.len() {
+            let va = VirtAddr(va + written);
+            let pte = self.fault_in(va.floor(), MapPermission::W)?;
+            let page_offset = va.page_offset();
+            let len = (PAGE_SIZE - page_offset).min(data.len() - written);
+            pte.ppn().bytes_array()[page_offset..page_offset + len].copy_from_slice(&data[written..written + len]);
+            written += len;
+        }
+        Ok(())
+    }
+
+    /// Physical address of `va` of a writable user area, which keeps pointing to the same word
+    /// as long as it's mapped: a copy-on-write page is copied first.
+    pub fn translate_writable(&mut self, va: VirtAddr) -> SysResult<PhysAddr> {
+        self.handle_page_fault(va, MapPermission::W)?;
+        let pte = self.page_table.translate(va.floor()).unwrap();
+        Ok(PhysAddr(PhysAddr::from(pte.ppn()).0 + va.page_offset()))
+    }
+
+    /// Map the code signal handlers return to at `SIGRETURN_TRAMPOLINE`, which makes the
+    /// `rt_sigreturn` syscall. There's no vDSO to provide it like Linux.
+    fn map_sigreturn_trampoline(&mut self) -> SysResult<()> {
+        // li a7, 139; ecall
+        static CODE: [u8; 8] = [0x93, 0x08, 0xb0, 0x08, 0x73, 0x00, 0x00, 0x00];
+        let area = MapArea::new(
+            SIGRETURN_TRAMPOLINE.into(),
+            (SIGRETURN_TRAMPOLINE + PAGE_SIZE).into(),
+            MapType::Framed,
+            MapPermission::R | MapPermission::X | MapPermission::U,
+            AreaType::Trampoline,
+        );
+        self.push(area, Some(&CODE), 0)
     }
 
     // Create a new memory set from an elf file
-    // return the memory set, the entry point and the user stack base
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_from_kernel();
+    // return the memory set and what the initial stack of the program needs to know about it
+    pub fn from_elf(elf_data: &'static [u8]) -> SysResult<(Self, ElfInfo)> {
+        let mut memory_set = Self::new_from_kernel()?;
 
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| SysError::ENOEXEC)?;
         let elf_header = elf.header;
 
         // check magic number
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf file");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(SysError::ENOEXEC);
+        }
 
         // load program headers
-        let ph_count = elf_header.pt2.ph_count();
         let entry_point = elf_header.pt2.entry_point() as usize;
-        let (max_end_vpn, _head_va) = memory_set.map_elf(&elf, VirtAddr(0));
-
+        let (max_end_vpn, phdr_va) = memory_set.map_elf(&elf, VirtAddr(0))?;
+        memory_set.map_sigreturn_trampoline()?;
 
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_heap_bottom: usize = max_end_va.into();
-        user_heap_bottom += PAGE_SIZE;
+        memory_set.heap_bottom = max_end_va.0 + PAGE_SIZE;
+        memory_set.brk = memory_set.heap_bottom;
 
-        (memory_set, entry_point, user_heap_bottom)
+        let elf_info = ElfInfo {
+            entry_point,
+            phdr: phdr_va.into(),
+            phent: elf_header.pt2.ph_entry_size() as usize,
+            phnum: elf_header.pt2.ph_count() as usize,
+        };
+        Ok((memory_set, elf_info))
     }
 }
 
+/// Drop the cached entries of the page table on all harts, as other threads of the process may be
+/// running on them.
+fn flush_tlb_all() {
+    unsafe { asm!("sfence.vma") };
+    sbi::remote_sfence_vma_all();
+}
+
+/// What a loaded program is told about itself by the auxiliary vector.
+pub struct ElfInfo {
+    pub entry_point: usize,
+    /// va of the program headers
+    pub phdr: usize,
+    pub phent: usize,
+    pub phnum: usize,
+}
+
 unsafe extern "C" {
     fn stext();
     fn etext();
@@ -1,9 +1,10 @@
 pub mod address;
 mod frame;
 mod heap_allocator;
-mod map_area;
+pub mod map_area;
 pub mod memory_set;
 mod paging;
+pub mod user_ptr;
 
 pub use memory_set::activate_kernel_space;
 
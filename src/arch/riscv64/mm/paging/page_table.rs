@@ -1,13 +1,16 @@
 use alloc::{vec, vec::Vec};
 
 use super::pte::{PTEFlags, PageTableEntry};
-use crate::arch::{
-    config::KERNEL_PGNUM_OFFSET,
-    mm::{
-        address::{PhysPageNum, VirtPageNum},
-        frame::{FrameTracker, frame_alloc},
-        memory_set::KERNEL_SPACE,
+use crate::{
+    arch::{
+        config::KERNEL_PGNUM_OFFSET,
+        mm::{
+            address::{PhysPageNum, VirtPageNum},
+            frame::{FrameTracker, frame_alloc},
+            memory_set::KERNEL_SPACE,
+        },
     },
+    error::{SysError, SysResult},
 };
 
 pub struct PageTable {
@@ -17,40 +20,52 @@ pub struct PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let frame = frame_alloc().unwrap();
-        Self {
+    pub fn new() -> SysResult<Self> {
+        let frame = frame_alloc().ok_or(SysError::ENOMEM)?;
+        Ok(Self {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
-    pub fn new_from_kernel() -> Self {
-        let frame = frame_alloc().unwrap();
+    pub fn new_from_kernel() -> SysResult<Self> {
+        let frame = frame_alloc().ok_or(SysError::ENOMEM)?;
         let locked_kernel = KERNEL_SPACE.lock();
         let kernel_root_ppn = locked_kernel.page_table.root_ppn;
         // 第一级页表
         let index = VirtPageNum(KERNEL_PGNUM_OFFSET).indexes()[0];
         frame.ppn.pte_array()[index..].copy_from_slice(&kernel_root_ppn.pte_array()[index..]);
-        PageTable {
+        Ok(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
     pub fn token(&self) -> usize {
         (8usize << 60) | self.root_ppn.0
     }
 
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
-        assert!(
-            !pte.is_valid(),
-            "vpn {:x}, va {:x} is mapped before mapping",
-            vpn.0,
-            vpn.0 << 12
-        );
-        *pte = PageTableEntry::new(ppn, flags); // TODO does flags need a mask ?
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> SysResult<()> {
+        let pte = self.find_pte_create(vpn).ok_or(SysError::ENOMEM)?;
+        if pte.is_valid() {
+            log::warn!("vpn {:x}, va {:x} is mapped before mapping", vpn.0, vpn.0 << 12);
+            return Err(SysError::EEXIST);
+        }
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        Ok(())
+    }
+
+    /// Allocate the page tables on the way to `vpn` without mapping it, e.g. so that a root entry
+    /// exists before it's copied into other page tables.
+    pub fn alloc_tables(&mut self, vpn: VirtPageNum) -> SysResult<()> {
+        self.find_pte_create(vpn).map(|_| ()).ok_or(SysError::ENOMEM)
+    }
+
+    /// Change the frame or the flags of a mapped page.
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {} is invalid before remapping", vpn.0);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
     }
 
     pub fn unmap(&mut self, vpn: VirtPageNum) {
@@ -74,7 +89,7 @@ impl PageTable {
                 break;
             }
             if !pte.is_valid() {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
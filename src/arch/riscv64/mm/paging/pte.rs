@@ -5,6 +5,7 @@ use crate::arch::mm::address::PhysPageNum;
 
 
 bitflags! {
+    #[derive(Clone, Copy)]
     pub struct PTEFlags: u16 {
         const V = 1 << 0;
         const R = 1 << 1;
@@ -39,11 +40,20 @@ impl PageTableEntry {
         ((self.bits >> 10) & ((1usize << 44) - 1)).into()
     }
 
-    fn flags(&self) -> PTEFlags {
+    pub fn flags(&self) -> PTEFlags {
         PTEFlags::from_bits((self.bits & ((1 << 9) - 1)) as u16).unwrap()
     }
 
     pub fn is_valid(&self) -> bool {
         !(self.flags() & PTEFlags::V).is_empty()
     }
+
+    pub fn is_writable(&self) -> bool {
+        self.flags().contains(PTEFlags::W)
+    }
+
+    /// The frame is shared with another address space until it's written.
+    pub fn is_cow(&self) -> bool {
+        self.flags().contains(PTEFlags::COW)
+    }
 }
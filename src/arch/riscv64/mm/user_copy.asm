@@ -0,0 +1,74 @@
+.attribute arch, "rv64gc"
+
+# record a fixup for the instruction at `insn` in the exception table
+.macro EXTABLE insn, fixup
+    .pushsection .extable, "a"
+    .balign 8
+    .dword \insn
+    .dword \fixup
+    .popsection
+.endm
+
+.section .text
+    .globl __user_copy
+    .globl __user_strncpy
+    .globl __user_cmpxchg32
+    .align 2
+
+# copy a2 bytes from a1 to a0, sstatus.SUM must be set
+# return 0, or -EFAULT if a fault happened
+__user_copy:
+    beqz a2, .Lcopy_done
+.Lcopy_load:
+    lb t0, 0(a1)
+    EXTABLE .Lcopy_load, .Luser_fault
+.Lcopy_store:
+    sb t0, 0(a0)
+    EXTABLE .Lcopy_store, .Luser_fault
+    addi a0, a0, 1
+    addi a1, a1, 1
+    addi a2, a2, -1
+    bnez a2, .Lcopy_load
+.Lcopy_done:
+    li a0, 0
+    ret
+
+# copy at most a2 bytes from user a1 to kernel a0 until a NUL, sstatus.SUM must be set
+# return the number of bytes before the NUL (a2 if there's no NUL), or -EFAULT if a fault happened
+__user_strncpy:
+    li t1, 0
+.Lstr_loop:
+    beq t1, a2, .Lstr_done
+.Lstr_load:
+    lbu t0, 0(a1)
+    EXTABLE .Lstr_load, .Luser_fault
+    beqz t0, .Lstr_done
+    sb t0, 0(a0)
+    addi a0, a0, 1
+    addi a1, a1, 1
+    addi t1, t1, 1
+    j .Lstr_loop
+.Lstr_done:
+    mv a0, t1
+    ret
+
+# replace the 32-bit user word at a0 with a2 if it equals a1 atomically, sstatus.SUM must be set
+# store the value found to kernel a3, return 0, or -EFAULT if a fault happened
+__user_cmpxchg32:
+    sext.w a1, a1
+.Lcmpxchg_load:
+    lr.w.aqrl t0, (a0)
+    EXTABLE .Lcmpxchg_load, .Luser_fault
+    bne t0, a1, .Lcmpxchg_done
+.Lcmpxchg_store:
+    sc.w.rl t1, a2, (a0)
+    EXTABLE .Lcmpxchg_store, .Luser_fault
+    bnez t1, .Lcmpxchg_load
+.Lcmpxchg_done:
+    sw t0, 0(a3)
+    li a0, 0
+    ret
+
+# a0 has been set to -EFAULT by trap_from_kernel
+.Luser_fault:
+    ret
@@ -0,0 +1,242 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    arch::global_asm,
+    marker::PhantomData,
+    mem::{MaybeUninit, size_of},
+};
+
+use riscv::register::sstatus;
+
+use crate::{
+    arch::{
+        config::{PAGE_SIZE, USER_SPACE_END},
+        mm::{
+            address::{VirtAddr, VirtPageNum},
+            map_area::MapPermission,
+        },
+        process::{current_process, current_trap_cx},
+    },
+    error::{SysError, SysResult},
+};
+
+global_asm!(include_str!("user_copy.asm"));
+
+unsafe extern "C" {
+    fn __user_copy(dst: *mut u8, src: *const u8, len: usize) -> isize;
+    fn __user_strncpy(dst: *mut u8, src: *const u8, len: usize) -> isize;
+    fn __user_cmpxchg32(addr: *mut u32, old: u32, new: u32, found: *mut u32) -> isize;
+}
+
+/// A typed pointer into the address space of the current process.
+///
+/// Every access is checked against the areas and the page table of the current process, so
+/// callers must not hold the inner lock of the current process.
+#[derive(Clone, Copy)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn new(addr: usize) -> Self {
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+
+    /// Pointer to the `count`-th element after this one.
+    pub fn add(&self, count: usize) -> Self {
+        Self::new(self.addr + count * size_of::<T>())
+    }
+
+    pub fn read(&self) -> SysResult<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        check_user_range(self.addr, size_of::<T>(), MapPermission::R)?;
+        unsafe {
+            user_copy(value.as_mut_ptr() as *mut u8, self.addr as *const u8, size_of::<T>())?;
+            Ok(value.assume_init())
+        }
+    }
+
+    pub fn write(&self, value: T) -> SysResult<()> {
+        check_user_range(self.addr, size_of::<T>(), MapPermission::W)?;
+        unsafe { user_copy(self.addr as *mut u8, &value as *const T as *const u8, size_of::<T>()) }
+    }
+}
+
+impl UserPtr<u32> {
+    /// Replace the value with `new` if it's `old` atomically, e.g. a futex word that user space
+    /// changes at the same time. Return the value found, which is `old` on success.
+    pub fn cmpxchg(&self, old: u32, new: u32) -> SysResult<u32> {
+        if self.addr % size_of::<u32>() != 0 {
+            return Err(SysError::EINVAL);
+        }
+        check_user_range(self.addr, size_of::<u32>(), MapPermission::R | MapPermission::W)?;
+        let mut found = 0;
+        let ret = unsafe {
+            sstatus::set_sum();
+            let ret = __user_cmpxchg32(self.addr as *mut u32, old, new, &mut found);
+            sstatus::clear_sum();
+            ret
+        };
+        match ret {
+            0 => Ok(found),
+            _ => Err(SysError::EFAULT),
+        }
+    }
+}
+
+impl UserPtr<u8> {
+    /// Read a NUL-terminated string, which is at most `max_len` bytes long without the NUL.
+    pub fn read_cstr(&self, max_len: usize) -> SysResult<String> {
+        let mut bytes = Vec::new();
+        let mut addr = self.addr;
+        loop {
+            // check page by page, the string may end before an unmapped page
+            let chunk_len = PAGE_SIZE - VirtAddr(addr).page_offset();
+            check_user_range(addr, chunk_len, MapPermission::R)?;
+            let old_len = bytes.len();
+            bytes.resize(old_len + chunk_len, 0);
+            let copied = unsafe { user_strncpy(bytes[old_len..].as_mut_ptr(), addr as *const u8, chunk_len)? };
+            bytes.truncate(old_len + copied);
+            if bytes.len() > max_len {
+                return Err(SysError::ENAMETOOLONG);
+            }
+            if copied < chunk_len {
+                break;
+            }
+            addr += chunk_len;
+        }
+        String::from_utf8(bytes).map_err(|_| SysError::EINVAL)
+    }
+}
+
+/// A byte buffer in the address space of the current process.
+#[derive(Clone, Copy)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new(addr: usize, len: usize) -> Self {
+        Self { addr, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The sub-buffer `[offset, offset + len)` of this buffer.
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        assert!(offset + len <= self.len);
+        Self::new(self.addr + offset, len)
+    }
+
+    /// Copy the head of this buffer into `dst`.
+    pub fn read(&self, dst: &mut [u8]) -> SysResult<()> {
+        assert!(dst.len() <= self.len);
+        copy_from_user(dst, self.addr)
+    }
+
+    /// Copy `src` into the head of this buffer.
+    pub fn write(&self, src: &[u8]) -> SysResult<()> {
+        assert!(src.len() <= self.len);
+        copy_to_user(self.addr, src)
+    }
+}
+
+/// Copy `dst.len()` bytes from user address `src`.
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> SysResult<()> {
+    check_user_range(src, dst.len(), MapPermission::R)?;
+    unsafe { user_copy(dst.as_mut_ptr(), src as *const u8, dst.len()) }
+}
+
+/// Copy `src` to user address `dst`.
+pub fn copy_to_user(dst: usize, src: &[u8]) -> SysResult<()> {
+    check_user_range(dst, src.len(), MapPermission::W)?;
+    unsafe { user_copy(dst as *mut u8, src.as_ptr(), src.len()) }
+}
+
+/// Make sure every page of `[start, start + len)` belongs to a user area with `perm` and is
+/// mapped in the page table of the current process.
+/// Pages are populated, the stack is grown and copy-on-write pages are copied for writing here, a
+/// fault from the kernel is never resolved.
+fn check_user_range(start: usize, len: usize, perm: MapPermission) -> SysResult<()> {
+    if len == 0 {
+        return Ok(());
+    }
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= USER_SPACE_END)
+        .ok_or(SysError::EFAULT)?;
+    let sp = current_trap_cx().x[2];
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let perm = perm | MapPermission::U;
+    for vpn in VirtAddr(start).floor().0..VirtAddr(end).ceil().0 {
+        let vpn = VirtPageNum(vpn);
+        let memory = &process_inner.memory;
+        // e.g. below the stack, which may grow to it
+        let Some(area) = memory.find_area(vpn) else {
+            process_inner.handle_page_fault(vpn.into(), perm - MapPermission::U, sp)?;
+            continue;
+        };
+        if !area.map_perm().contains(perm) {
+            return Err(SysError::EFAULT);
+        }
+        let ready = memory
+            .page_table
+            .translate(vpn)
+            .filter(|pte| pte.is_valid())
+            .is_some_and(|pte| !pte.is_cow() || !perm.contains(MapPermission::W));
+        if !ready {
+            process_inner.handle_page_fault(vpn.into(), perm - MapPermission::U, sp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy `len` bytes with `sstatus.SUM` set.
+/// A fault on the user side, e.g. the area is unmapped by another thread after checking, is
+/// fixed up by `trap_from_kernel` and turned into `EFAULT`.
+unsafe fn user_copy(dst: *mut u8, src: *const u8, len: usize) -> SysResult<()> {
+    let ret = unsafe {
+        sstatus::set_sum();
+        let ret = __user_copy(dst, src, len);
+        sstatus::clear_sum();
+        ret
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(SysError::EFAULT),
+    }
+}
+
+/// Copy at most `len` bytes from user `src` until a NUL, with `sstatus.SUM` set.
+/// Return the number of bytes before the NUL, or `len` if there's no NUL.
+unsafe fn user_strncpy(dst: *mut u8, src: *const u8, len: usize) -> SysResult<usize> {
+    let ret = unsafe {
+        sstatus::set_sum();
+        let ret = __user_strncpy(dst, src, len);
+        sstatus::clear_sum();
+        ret
+    };
+    match ret {
+        0.. => Ok(ret as usize),
+        _ => Err(SysError::EFAULT),
+    }
+}
@@ -1,3 +1,6 @@
+use crate::arch::trap::trap_return;
+
+/// Callee-saved registers of a kernel control flow, saved and restored by `__switch`.
 #[repr(C)]
 pub struct ThreadContext {
     ra: usize,
@@ -6,11 +9,20 @@ pub struct ThreadContext {
 }
 
 impl ThreadContext {
-    pub fn zero_init() -> Self {
+    pub const fn zero_init() -> Self {
         Self {
             ra: 0,
             sp: 0,
             s: [0; 12],
         }
     }
+
+    /// A context that starts at `trap_return` on the kernel stack `kstack_top`.
+    pub fn goto_trap_return(kstack_top: usize) -> Self {
+        Self {
+            ra: trap_return as usize,
+            sp: kstack_top,
+            s: [0; 12],
+        }
+    }
 }
@@ -0,0 +1,281 @@
+//! Futexes, see `futex(2)`.
+//!
+//! Waiters are queued by the physical address of the futex word, so threads sharing the page meet
+//! in the same queue whatever process and address they use.
+
+use alloc::{
+    collections::{BTreeMap, btree_map::Entry},
+    sync::Arc,
+};
+use core::mem::size_of;
+
+use spin::Mutex;
+
+use super::{
+    current_process, current_thread,
+    manager::PROCESS_MANAGER,
+    scheduler::{pi_boost, pi_restore},
+    signal::RestartBlock,
+    tcb::ThreadControlBlock,
+    wait_queue::{WaitQueue, WaitResult},
+};
+use crate::{
+    arch::mm::{address::VirtAddr, user_ptr::UserPtr},
+    error::{SysError, SysResult},
+};
+
+/// Set in the word of a PI futex when threads may be blocked on it, so that the owner has to
+/// unlock it by `futex_unlock_pi`.
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+/// The tid of the owner of a PI futex.
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// Wait queues by the key of the futex word.
+///
+/// A queue is only used by the ones holding an `Arc` of it, got by `get_queue` with the lock held,
+/// and removed by the last of them in `put_queue`. Requeueing is done with the lock held as well.
+static FUTEXES: Mutex<BTreeMap<usize, Arc<WaitQueue>>> = Mutex::new(BTreeMap::new());
+
+/// The physical address of the futex word at `uaddr` of the current process.
+fn futex_key(uaddr: usize) -> SysResult<usize> {
+    if uaddr % size_of::<u32>() != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    Ok(process_inner.memory.translate_writable(VirtAddr(uaddr))?.0)
+}
+
+fn get_queue(key: usize) -> Arc<WaitQueue> {
+    FUTEXES
+        .lock()
+        .entry(key)
+        .or_insert_with(|| Arc::new(WaitQueue::new()))
+        .clone()
+}
+
+/// Give back a queue got by `get_queue`, it's removed if no one else uses it.
+/// Blocked threads hold it as well, see `ThreadControlBlockInner::wait_queue`.
+fn put_queue(key: usize, queue: Arc<WaitQueue>) {
+    let mut futexes = FUTEXES.lock();
+    drop(queue);
+    if let Entry::Occupied(entry) = futexes.entry(key) {
+        if Arc::strong_count(entry.get()) == 1 {
+            entry.remove();
+        }
+    }
+}
+
+/// Block until woken up by `futex_wake` if the word at `uaddr` is `val`, or until `deadline` in
+/// timer cycles if there's one.
+pub fn futex_wait(uaddr: usize, val: u32, deadline: Option<usize>) -> SysResult<usize> {
+    let key = futex_key(uaddr)?;
+    let queue = get_queue(key);
+    let mut error = SysError::EAGAIN;
+    let result = queue.wait_if(
+        || match UserPtr::<u32>::new(uaddr).read() {
+            Ok(value) => value == val,
+            Err(err) => {
+                error = err;
+                false
+            },
+        },
+        deadline,
+    );
+    put_queue(key, queue);
+    match result {
+        WaitResult::NotBlocked => Err(error),
+        WaitResult::Woken => Ok(0),
+        WaitResult::TimedOut => Err(SysError::ETIMEDOUT),
+        // the timeout is relative, so it's restarted until the same deadline, see `sys_nanosleep`
+        WaitResult::Interrupted => match deadline {
+            Some(deadline) => {
+                current_thread()
+                    .inner_exclusive_access()
+                    .set_restart_block(RestartBlock::FutexWait { uaddr, val, deadline });
+                Err(SysError::ERESTART_RESTARTBLOCK)
+            },
+            None => Err(SysError::ERESTARTSYS),
+        },
+    }
+}
+
+/// Wake up at most `count` waiters of the futex at `uaddr`, return how many are woken up.
+pub fn futex_wake(uaddr: usize, count: usize) -> SysResult<usize> {
+    let key = futex_key(uaddr)?;
+    let Some(queue) = FUTEXES.lock().get(&key).cloned() else {
+        return Ok(0);
+    };
+    let woken = queue.wake(count);
+    put_queue(key, queue);
+    Ok(woken)
+}
+
+/// Wake up at most `count` waiters of the futex at `uaddr`, and move at most `count2` of the rest
+/// to the futex at `uaddr2`. If `cmp` is given, do nothing but fail with EAGAIN unless the word
+/// at `uaddr` is still `cmp`. Return how many are woken up or moved.
+pub fn futex_requeue(uaddr: usize, count: usize, count2: usize, uaddr2: usize, cmp: Option<u32>) -> SysResult<usize> {
+    let key = futex_key(uaddr)?;
+    let key2 = futex_key(uaddr2)?;
+    let mut futexes = FUTEXES.lock();
+    if cmp.is_some_and(|cmp| UserPtr::<u32>::new(uaddr).read() != Ok(cmp)) {
+        return Err(SysError::EAGAIN);
+    }
+    let Some(queue) = futexes.get(&key).cloned() else {
+        return Ok(0);
+    };
+    let queue2 = futexes
+        .entry(key2)
+        .or_insert_with(|| Arc::new(WaitQueue::new()))
+        .clone();
+    let woken = queue.wake(count);
+    let moved = match key == key2 {
+        true => 0,
+        false => queue.requeue(&queue2, count2),
+    };
+    drop(futexes);
+    put_queue(key, queue);
+    put_queue(key2, queue2);
+    Ok(woken + moved)
+}
+
+/// Take the PI futex at `uaddr`, whose word is 0 when it's free and the tid of the owner
+/// otherwise. If it's taken by another thread, block until it's handed over by
+/// `futex_unlock_pi`, or until `deadline` in timer cycles if there's one, or fail with EAGAIN
+/// if `try_only`.
+///
+/// The owner inherits the priority of the waiters, so that threads in between can't keep it
+/// from unlocking.
+pub fn futex_lock_pi(uaddr: usize, deadline: Option<usize>, try_only: bool) -> SysResult<usize> {
+    let key = futex_key(uaddr)?;
+    let queue = get_queue(key);
+    let result = lock_pi(key, UserPtr::new(uaddr), &queue, deadline, try_only);
+    put_queue(key, queue);
+    result
+}
+
+fn lock_pi(
+    key: usize,
+    word: UserPtr<u32>,
+    queue: &Arc<WaitQueue>,
+    deadline: Option<usize>,
+    try_only: bool,
+) -> SysResult<usize> {
+    let tid = current_thread().tid() as u32;
+    let mut value = word.read()?;
+    loop {
+        let owner = value & FUTEX_TID_MASK;
+        if owner == 0 {
+            // the ones still blocked have to be woken up by whoever unlocks it
+            let waiters = match queue.is_empty() {
+                true => 0,
+                false => FUTEX_WAITERS,
+            };
+            let found = word.cmpxchg(value, tid | waiters)?;
+            if found == value {
+                return Ok(0);
+            }
+            value = found;
+            continue;
+        }
+        if owner == tid {
+            return Err(SysError::EDEADLK);
+        }
+        if try_only {
+            return Err(SysError::EAGAIN);
+        }
+        // so that the owner can't unlock it in user space without waking us up
+        if value & FUTEX_WAITERS == 0 {
+            let found = word.cmpxchg(value, value | FUTEX_WAITERS)?;
+            if found != value {
+                value = found;
+                continue;
+            }
+            value |= FUTEX_WAITERS;
+        }
+        let owner = PROCESS_MANAGER.get_thread(owner as usize).ok_or(SysError::ESRCH)?;
+        let prio = current_thread().inner_exclusive_access().sched_entity().prio();
+        // boosted with the queue locked, so it's done before the owner hands it over and restores
+        let result = queue.wait_if(
+            || {
+                let still_owned = word.read() == Ok(value);
+                if still_owned {
+                    add_pi_futex(&owner, key);
+                    pi_boost(&owner, prio);
+                }
+                still_owned
+            },
+            deadline,
+        );
+        drop(owner);
+        match result {
+            // handed over by `futex_unlock_pi`
+            WaitResult::Woken => return Ok(0),
+            WaitResult::TimedOut => return Err(SysError::ETIMEDOUT),
+            // the deadline is absolute, so it can always be restarted like Linux
+            WaitResult::Interrupted => return Err(SysError::ERESTARTNOINTR),
+            // unlocked or taken by another thread in the meantime
+            WaitResult::NotBlocked => value = word.read()?,
+        }
+    }
+}
+
+/// Release the PI futex at `uaddr` held by the current thread, and hand it over to the most
+/// favorable waiter if there's one, so that a less favorable thread can't take it first.
+pub fn futex_unlock_pi(uaddr: usize) -> SysResult<usize> {
+    let word = UserPtr::<u32>::new(uaddr);
+    let key = futex_key(uaddr)?;
+    let thread = current_thread();
+    if word.read()? & FUTEX_TID_MASK != thread.tid() as u32 {
+        return Err(SysError::EPERM);
+    }
+
+    thread
+        .inner_exclusive_access()
+        .pi_futexes_mut()
+        .retain(|&held| held != key);
+    let queue = get_queue(key);
+    let mut result = Ok(());
+    // with the queue locked, so that a thread going to block on the old word finds it changed
+    let next = queue.wake_one_with(|next, more| {
+        let waiters = match more {
+            true => FUTEX_WAITERS,
+            false => 0,
+        };
+        result = set_futex_word(word, next.map_or(0, |next| next.tid() as u32 | waiters));
+    });
+    pi_restore(&thread, held_pi_prio(&thread));
+    if let (Some(next), Some(prio)) = (next, queue.highest_prio()) {
+        add_pi_futex(&next, key);
+        pi_boost(&next, prio);
+    }
+    put_queue(key, queue);
+    result.map(|_| 0)
+}
+
+/// Record that `owner` holds the PI futex of `key` which others block on.
+fn add_pi_futex(owner: &Arc<ThreadControlBlock>, key: usize) {
+    let mut owner_inner = owner.inner_exclusive_access();
+    if !owner_inner.pi_futexes().contains(&key) {
+        owner_inner.pi_futexes_mut().push(key);
+    }
+}
+
+/// The most favorable priority of the threads blocked on the PI futexes held by `owner`.
+fn held_pi_prio(owner: &Arc<ThreadControlBlock>) -> Option<i32> {
+    let keys = owner.inner_exclusive_access().pi_futexes().to_vec();
+    let futexes = FUTEXES.lock();
+    keys.iter().filter_map(|key| futexes.get(key)?.highest_prio()).min()
+}
+
+/// Store `new` to a futex word, which waiters may be setting `FUTEX_WAITERS` in at the same time.
+fn set_futex_word(word: UserPtr<u32>, new: u32) -> SysResult<()> {
+    let mut value = word.read()?;
+    loop {
+        let found = word.cmpxchg(value, new)?;
+        if found == value {
+            return Ok(());
+        }
+        value = found;
+    }
+}
@@ -0,0 +1,75 @@
+use core::arch::asm;
+
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    arch::{
+        config::{KERNEL_STACK_AREA_BASE, KERNEL_STACK_SIZE, KERNEL_STACK_SLOT_SIZE},
+        mm::{
+            address::VirtAddr,
+            map_area::{AreaType, MapPermission},
+            memory_set::KERNEL_SPACE,
+        },
+        sbi,
+        utils::QueueAllocator,
+    },
+    error::{SysError, SysResult},
+};
+
+// `__trap_from_kernel` checks bit 14 of `sp` to find out whether it's in a guard
+const _: () = assert!(KERNEL_STACK_SIZE == 1 << 14);
+
+/// Slots in the 1 GiB kernel stack area.
+const KERNEL_STACK_SLOTS: usize = (1 << 30) / KERNEL_STACK_SLOT_SIZE;
+
+lazy_static! {
+    static ref KSTACK_ALLOCATOR: Mutex<QueueAllocator> = Mutex::new(QueueAllocator::new());
+}
+
+/// Kernel stack of a thread, mapped in `KERNEL_SPACE` above an unmapped guard.
+pub struct KernelStack {
+    slot: usize,
+}
+
+impl KernelStack {
+    pub fn new() -> SysResult<Self> {
+        let slot = KSTACK_ALLOCATOR.lock().alloc();
+        if slot >= KERNEL_STACK_SLOTS {
+            KSTACK_ALLOCATOR.lock().dealloc(slot);
+            return Err(SysError::ENOMEM);
+        }
+        let kstack = Self { slot };
+        let bottom = kstack.bottom();
+        KERNEL_SPACE
+            .lock()
+            .insert_framed_area(
+                bottom.into(),
+                (bottom + KERNEL_STACK_SIZE).into(),
+                MapPermission::R | MapPermission::W,
+                AreaType::Stack,
+            )
+            .inspect_err(|_| KSTACK_ALLOCATOR.lock().dealloc(slot))?;
+        Ok(kstack)
+    }
+
+    fn bottom(&self) -> usize {
+        KERNEL_STACK_AREA_BASE + self.slot * KERNEL_STACK_SLOT_SIZE + KERNEL_STACK_SLOT_SIZE - KERNEL_STACK_SIZE
+    }
+
+    pub fn top(&self) -> usize {
+        self.bottom() + KERNEL_STACK_SIZE
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        KERNEL_SPACE
+            .lock()
+            .remove_area_with_start_vpn(VirtAddr::from(self.bottom()).floor());
+        // the slot may get frames of its own next time, no hart should see the old ones
+        unsafe { asm!("sfence.vma") };
+        sbi::remote_sfence_vma_all();
+        KSTACK_ALLOCATOR.lock().dealloc(self.slot);
+    }
+}
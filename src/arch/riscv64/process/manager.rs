@@ -0,0 +1,69 @@
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use spin::Mutex;
+
+use super::{pcb::ProcessControlBlock, tcb::ThreadControlBlock};
+
+pub static PROCESS_MANAGER: ProcessManager = ProcessManager::new();
+
+/// Every process that's not reaped yet by pid, and every thread that's not exited by tid.
+/// It doesn't keep them alive, their parents and processes do.
+pub struct ProcessManager {
+    processes: Mutex<BTreeMap<usize, Weak<ProcessControlBlock>>>,
+    threads: Mutex<BTreeMap<usize, Weak<ThreadControlBlock>>>,
+}
+
+impl ProcessManager {
+    const fn new() -> Self {
+        Self {
+            processes: Mutex::new(BTreeMap::new()),
+            threads: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn add_process(&self, pid: usize, process: &Arc<ProcessControlBlock>) {
+        self.processes.lock().insert(pid, Arc::downgrade(process));
+    }
+
+    pub fn remove_process(&self, pid: usize) {
+        self.processes.lock().remove(&pid);
+    }
+
+    pub fn get_process(&self, pid: usize) -> Option<Arc<ProcessControlBlock>> {
+        self.processes.lock().get(&pid).and_then(Weak::upgrade)
+    }
+
+    pub fn add_thread(&self, tid: usize, thread: &Arc<ThreadControlBlock>) {
+        self.threads.lock().insert(tid, Arc::downgrade(thread));
+    }
+
+    pub fn remove_thread(&self, tid: usize) {
+        self.threads.lock().remove(&tid);
+    }
+
+    pub fn get_thread(&self, tid: usize) -> Option<Arc<ThreadControlBlock>> {
+        self.threads.lock().get(&tid).and_then(Weak::upgrade)
+    }
+
+    /// Threads that have not exited, which `RLIMIT_NPROC` limits.
+    pub fn thread_count(&self) -> usize {
+        self.threads.lock().len()
+    }
+
+    /// All processes in the order of pid, e.g. for `kill(-1)`.
+    pub fn processes(&self) -> Vec<Arc<ProcessControlBlock>> {
+        self.processes.lock().values().filter_map(Weak::upgrade).collect()
+    }
+
+    /// The processes in group `pgid`, which is gone once it's empty.
+    pub fn process_group(&self, pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+        self.processes()
+            .into_iter()
+            .filter(|process| process.pgid() == pgid)
+            .collect()
+    }
+}
@@ -1,11 +1,86 @@
-use crate::{arch::process::pcb::ProcessControlBlock, loader::get_app_data_by_name};
+use alloc::sync::Arc;
 
-mod context;
+pub use futex::{futex_lock_pi, futex_requeue, futex_unlock_pi, futex_wait, futex_wake};
+pub use manager::PROCESS_MANAGER;
+pub use pcb::{ProcessControlBlock, WaitOptions, add_initproc};
+pub use scheduler::{
+    NICE_MAX, NICE_MIN, RT_PRIORITY_MAX, RunQueue, SchedPolicy, add_thread, need_resched, run_threads, scheduler_tick,
+    suspend_current_and_run_next, update_sched_entity,
+};
+pub use tcb::{ThreadControlBlock, ThreadStatus};
+pub use tty::{poll_console, read_console};
+pub use wait_queue::{WaitResult, check_timers, sleep_until};
+
+use crate::arch::{cpu::local_cpu_context, mm::user_ptr::UserPtr, trap::context::TrapContext};
+
+pub mod context;
+mod futex;
+mod kernel_stack;
+mod manager;
 mod pcb;
+pub mod rlimit;
+mod scheduler;
+pub mod signal;
+mod switch;
 mod tcb;
 mod thread_user_res;
+mod tty;
+mod user_stack;
+mod wait_queue;
+
+pub fn current_thread() -> Arc<ThreadControlBlock> {
+    local_cpu_context().current().unwrap()
+}
+
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_thread().process()
+}
+
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    current_thread().inner_exclusive_access().get_trap_cx()
+}
+
+pub fn current_trap_cx_user_va() -> usize {
+    current_thread().inner_exclusive_access().res().trap_cx_user_va()
+}
+
+pub fn current_user_token() -> usize {
+    current_process().get_user_token()
+}
+
+/// Exit the current thread and release its user resources.
+pub fn exit_current(exit_code: i32) -> ! {
+    let thread = current_thread();
+    let clear_child_tid = thread.inner_exclusive_access().clear_child_tid();
+    if clear_child_tid != 0 {
+        // like Linux, it's up to the user whether the address is valid
+        let _ = UserPtr::<i32>::new(clear_child_tid).write(0);
+        // e.g. pthread_join waits on it
+        let _ = futex_wake(clear_child_tid, 1);
+    }
+    PROCESS_MANAGER.remove_thread(thread.tid());
+    let mut thread_inner = thread.inner_exclusive_access();
+    thread_inner.set_status(ThreadStatus::Exited);
+    thread_inner.set_exit_code(exit_code);
+    let res = thread_inner.take_res();
+    drop(thread_inner);
 
-pub fn add_initproc() {
-    let elf_data = get_app_data_by_name("initproc").unwrap();
-    pcb::ProcessControlBlock::init_initproc(elf_data);
+    let process = thread.process();
+    let mut is_last = false;
+    if let Some(res) = &res {
+        let mut process_inner = process.inner_exclusive_access();
+        let removed = process_inner.remove_thread(res.tid());
+        is_last = process_inner.thread_count() == 0;
+        drop(process_inner);
+        drop(removed);
+        process.thread_exited();
+    }
+    // the process lock is taken when dropping them
+    drop(res);
+    if is_last {
+        process.exit(exit_code);
+    }
+    drop(process);
+    drop(thread);
+    scheduler::exit_and_run_next()
 }
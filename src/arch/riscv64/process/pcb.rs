@@ -1,25 +1,53 @@
 use alloc::{
+    string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
 };
-use core::cell::RefMut;
+use core::{
+    mem, ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use bitflags::bitflags;
 use lazy_static::lazy_static;
-use spin::Mutex;
+use spin::{Mutex, Once};
 
-use super::tcb::ThreadControlBlock;
+use super::{
+    current_thread, current_trap_cx,
+    manager::PROCESS_MANAGER,
+    rlimit::{INIT_RLIMITS, RLIM_NLIMITS, RLIMIT_AS, RLIMIT_CORE, RLIMIT_CPU, RLIMIT_NPROC, RLIMIT_STACK, RLimit},
+    scheduler::{add_thread, wakeup_thread},
+    signal::{
+        NSIG, SIG_IGN, SIGCHLD, SIGKILL, SIGTTOU, SIGXCPU, SigAction, SigActionFlags, SigInfo, SigPending,
+        is_blocked_or_ignored, send_signal, send_signal_to_group,
+    },
+    tcb::ThreadControlBlock,
+    user_stack::{UserStack, init_user_stack},
+    wait_queue::{WaitQueue, WaitResult},
+};
 use crate::{
     arch::{
-        mm::memory_set::{self, MemorySet},
-        trap::context::TrapContext,
+        config::USER_STACK_TOP,
+        mm::{address::VirtAddr, map_area::MapPermission, memory_set::MemorySet},
+        timer::TICKS_PER_SEC,
+        trap::{context::TrapContext, trap_handler},
         utils::QueueAllocator,
     },
+    error::{SysError, SysResult},
     loader::get_app_data_by_name,
 };
 
 
 pub struct ProcessControlBlock {
     pid: Pid,
+    /// threads in wait4, woken up when a child exits, stops or continues
+    child_exit: Arc<WaitQueue>,
+    /// threads of the process while it's stopped, woken up when it's continued
+    continued: Arc<WaitQueue>,
+    /// the thread in execve, woken up when another thread exits
+    thread_exit: Arc<WaitQueue>,
+    /// the parent in vfork, woken up when this child calls execve or exits
+    vfork_done: Arc<WaitQueue>,
     inner: Mutex<ProcessControlBlockInner>,
 }
 
@@ -27,29 +55,68 @@ pub struct ProcessControlBlockInner {
     parent: Option<Weak<ProcessControlBlock>>,
     children: Vec<Arc<ProcessControlBlock>>,
     status: ProcessStatus,
+    /// in the format of `wstatus` of wait4, valid once the process is a zombie
     exit_code: i32,
+    /// exit_group is called, all threads are exiting with `exit_code`
+    exiting: bool,
+    /// tid of the thread in execve, all the others are exiting
+    exec_tid: Option<usize>,
+    /// forked by vfork, and the parent is waiting until it calls execve or exits
+    in_vfork: bool,
+    /// stopped by a stop signal until SIGCONT
+    stopped: bool,
+    /// stopped or continued in the format of `wstatus` of wait4, until it's reported to the parent
+    job_status: Option<i32>,
+    pgid: usize,
+    sid: usize,
+    /// the console is the controlling terminal of its session
+    has_console: bool,
     threads: Vec<Option<Arc<ThreadControlBlock>>>,
     pub tid_allocator: Mutex<QueueAllocator>,
-    memory: MemorySet,
+    pub memory: MemorySet,
+    /// by signal number minus 1
+    pub sig_actions: [SigAction; NSIG],
+    /// signals sent to the process, taken by any thread not blocking them
+    pub sig_pending: SigPending,
+    /// by resource, see `rlimit`
+    rlimits: [RLimit; RLIM_NLIMITS],
+    /// timer ticks the threads have run in user mode, which `RLIMIT_CPU` limits
+    cpu_ticks: usize,
     // fd_table: Vec<Option<Arc<File>>>,
     // cwd: Arc<Dir>,
 }
 
+pub static INITPROC: Once<Arc<ProcessControlBlock>> = Once::new();
+
+/// The foreground process group of the console, which may read from it and is sent the signals
+/// typed on it. It's the one of initproc at first, which drops those signals as it has no
+/// handlers for them.
+static CONSOLE_FOREGROUND: AtomicUsize = AtomicUsize::new(0);
+
+pub fn console_foreground() -> usize {
+    CONSOLE_FOREGROUND.load(Ordering::Relaxed)
+}
+
 /// Add init process to the manager
-pub fn add_initproc() {
-    let elf_data = get_app_data_by_name("initproc").unwrap();
+pub fn add_initproc() -> SysResult<()> {
+    let elf_data = get_app_data_by_name("initproc").ok_or(SysError::ENOENT)?;
 
-    let _init_proc = ProcessControlBlock::init_initproc(elf_data);
-    // PROCESS_MANAGER.add_process(_init_proc.pid(), &_init_proc);
+    let init_proc = INITPROC.try_call_once(|| ProcessControlBlock::init_initproc(elf_data))?;
+    PROCESS_MANAGER.add_process(init_proc.pid(), init_proc);
+    CONSOLE_FOREGROUND.store(init_proc.pgid(), Ordering::Relaxed);
+    add_thread(init_proc.inner_exclusive_access().get_thread(0));
+    Ok(())
 }
 
-struct Pid(usize);
+/// Pids and tids of threads other than the main ones share the same space, like Linux.
+pub(super) struct Pid(pub(super) usize);
 
 lazy_static! {
-    static ref PID_ALLOCATOR: Mutex<QueueAllocator> = Mutex::new(QueueAllocator::new());
+    // pid 0 means the caller or its process group in many syscalls, so it's never used
+    static ref PID_ALLOCATOR: Mutex<QueueAllocator> = Mutex::new(QueueAllocator::new_from(1));
 }
 
-fn pid_alloc() -> Pid {
+pub(super) fn pid_alloc() -> Pid {
     Pid(PID_ALLOCATOR.lock().alloc())
 }
 
@@ -59,8 +126,23 @@ impl Drop for Pid {
     }
 }
 
+bitflags! {
+    /// Options of wait4, which changes of children are reported besides exits
+    #[derive(Clone, Copy)]
+    pub struct WaitOptions: usize {
+        const NOHANG = 1;
+        const UNTRACED = 2;
+        const CONTINUED = 8;
+    }
+}
+
+/// `wstatus` of wait4 for a child continued by SIGCONT
+const WSTATUS_CONTINUED: i32 = 0xffff;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
 enum ProcessStatus {
     Normal,
+    /// all threads have exited, waiting for the parent to reap it
     Zombie,
     Exited,
 }
@@ -68,36 +150,715 @@ enum ProcessStatus {
 impl ProcessControlBlock {
     // only initproc can be created by hand
     // other process should be created by fork or exec
-    pub fn init_initproc(elf_data: &[u8]) -> Arc<Self> {
-        let (memory_set, entry_point, user_heap_bottom) = MemorySet::from_elf(elf_data);
+    pub fn init_initproc(elf_data: &'static [u8]) -> SysResult<Arc<Self>> {
+        let (memory_set, elf_info) = MemorySet::from_elf(elf_data)?;
+        let pid = pid_alloc();
+        let pgid = pid.0;
         let pcb = Arc::new(Self {
-            pid: pid_alloc(),
+            pid,
+            child_exit: Arc::new(WaitQueue::new()),
+            continued: Arc::new(WaitQueue::new()),
+            thread_exit: Arc::new(WaitQueue::new()),
+            vfork_done: Arc::new(WaitQueue::new()),
             inner: Mutex::new(ProcessControlBlockInner {
                 parent: None,
                 children: Vec::new(),
                 status: ProcessStatus::Normal,
                 exit_code: 0,
+                exiting: false,
+                exec_tid: None,
+                in_vfork: false,
+                stopped: false,
+                job_status: None,
+                // the leader of the first session, which has the console
+                pgid,
+                sid: pgid,
+                has_console: true,
                 threads: Vec::new(),
                 tid_allocator: Mutex::new(QueueAllocator::new()),
                 memory: memory_set,
+                sig_actions: [SigAction::DEFAULT; NSIG],
+                sig_pending: SigPending::new(),
+                rlimits: INIT_RLIMITS,
+                cpu_ticks: 0,
             }),
         });
-        pcb
+
+        // create the main thread, whose trap context is initialized here
+        let thread = Arc::new(ThreadControlBlock::new(pcb.clone(), USER_STACK_TOP, true)?);
+        let ustack_top = thread.inner_exclusive_access().res().ustack_top();
+        let user_stack = init_user_stack(
+            &mut pcb.inner_exclusive_access().memory,
+            ustack_top,
+            &["initproc".to_string()],
+            &[],
+            &elf_info,
+        )?;
+        *thread.inner_exclusive_access().get_trap_cx() = Self::init_trap_cx(elf_info.entry_point, &user_stack);
+        pcb.insert_thread(thread);
+        Ok(pcb)
+    }
+
+    fn init_trap_cx(entry_point: usize, user_stack: &UserStack) -> TrapContext {
+        // kernel_sp is filled in each time we return to user
+        let mut trap_cx = TrapContext::app_init_context(entry_point, user_stack.sp, 0, trap_handler as usize);
+        // the layout on the stack is what the ABI asks for, these are for a `_start` in Rust
+        trap_cx.x[10] = user_stack.argc;
+        trap_cx.x[11] = user_stack.argv;
+        trap_cx.x[12] = user_stack.envp;
+        trap_cx
     }
 
     pub fn inner_exclusive_access(&self) -> spin::MutexGuard<'_, ProcessControlBlockInner> {
         self.inner.lock()
     }
 
-    fn add_thread(thread: ThreadControlBlock) {
-        // todo: add thread to current PCB
+    /// Put a new thread into the slot of its user resources and make it visible by tid.
+    fn insert_thread(&self, thread: Arc<ThreadControlBlock>) {
+        PROCESS_MANAGER.add_thread(thread.tid(), &thread);
+        let idx = thread.inner_exclusive_access().res().tid();
+        let mut inner = self.inner_exclusive_access();
+        if inner.threads.len() <= idx {
+            inner.threads.resize(idx + 1, None);
+        }
+        inner.threads[idx] = Some(thread);
+    }
+
+    /// Create a thread with a copy of the trap context of the calling one, which returns 0 from
+    /// the syscall on `stack`. It's up to the caller to start it.
+    pub fn clone_thread(self: &Arc<Self>, stack: usize, tls: Option<usize>) -> SysResult<Arc<ThreadControlBlock>> {
+        self.check_nproc()?;
+        let thread = current_thread();
+        let thread_inner = thread.inner_exclusive_access();
+        let user_stack_base = thread_inner.get_user_stack_base();
+        let sched_entity = thread_inner.sched_entity().fork();
+        let sig_mask = thread_inner.sig_mask();
+        drop(thread_inner);
+        let new_thread = Arc::new(ThreadControlBlock::new(self.clone(), user_stack_base, true)?);
+        let mut new_thread_inner = new_thread.inner_exclusive_access();
+        *new_thread_inner.sched_entity_mut() = sched_entity;
+        new_thread_inner.set_sig_mask(sig_mask);
+        let trap_cx = new_thread_inner.get_trap_cx();
+        *trap_cx = *current_trap_cx();
+        trap_cx.x[10] = 0;
+        // a stack of its own is mapped anyway, use it if the caller doesn't give one
+        trap_cx.x[2] = match stack {
+            0 => new_thread_inner.res().ustack_top(),
+            stack => stack,
+        };
+        if let Some(tls) = tls {
+            trap_cx.x[4] = tls;
+        }
+        drop(new_thread_inner);
+        self.insert_thread(new_thread.clone());
+        Ok(new_thread)
+    }
+
+    /// Replace the address space with the program in `elf_data`, which the calling thread goes on
+    /// running from its entry. Nothing is changed on failure, e.g. ENOMEM if the program doesn't
+    /// fit in `RLIMIT_AS`.
+    pub fn exec(self: &Arc<Self>, elf_data: &'static [u8], args: &[String], envs: &[String]) -> SysResult<()> {
+        let (mut memory_set, elf_info) = MemorySet::from_elf(elf_data)?;
+        let thread = current_thread();
+        let thread_inner = thread.inner_exclusive_access();
+        let res = thread_inner.res();
+        res.map_user_res(&mut memory_set)?;
+        if memory_set.user_size() > self.rlimit(RLIMIT_AS).cur {
+            return Err(SysError::ENOMEM);
+        }
+        let trap_cx_ppn = memory_set
+            .page_table
+            .translate(VirtAddr::from(res.trap_cx_user_va()).floor())
+            .unwrap()
+            .ppn();
+        let user_stack = init_user_stack(&mut memory_set, res.ustack_top(), args, envs, &elf_info)?;
+        drop(thread_inner);
+        drop(thread);
+
+        // like Linux, the other threads are gone before the program is replaced, so none of them
+        // runs it or releases its user resources from it
+        self.kill_other_threads()?;
+        // CLOEXEC fds are to be closed here once there's a fd table, see TODO.md
+        let mut inner = self.inner_exclusive_access();
+        let old_memory_set = mem::replace(&mut inner.memory, memory_set);
+        // the hart is still on the page table of the old one
+        inner.memory.activate();
+        // the handlers are gone with the old program, while ignored signals stay ignored
+        for action in inner.sig_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::DEFAULT;
+            }
+        }
+        drop(inner);
+        drop(old_memory_set);
+        self.end_vfork();
+
+        let thread = current_thread();
+        let mut thread_inner = thread.inner_exclusive_access();
+        thread_inner.set_trap_cx_ppn(trap_cx_ppn);
+        *thread_inner.get_trap_cx() = Self::init_trap_cx(elf_info.entry_point, &user_stack);
+        Ok(())
+    }
+
+    /// Make the other threads exit for execve and wait until they have. EAGAIN if the process is
+    /// exiting or another thread is in execve, which the calling thread exits for on its way back
+    /// to user.
+    fn kill_other_threads(&self) -> SysResult<()> {
+        let tid = current_thread().tid();
+        let mut inner = self.inner_exclusive_access();
+        if inner.exiting || inner.exec_tid.is_some() {
+            return Err(SysError::EAGAIN);
+        }
+        inner.exec_tid = Some(tid);
+        let threads = inner.threads();
+        drop(inner);
+        // blocked ones are woken up to find out, the others exit when they trap into the kernel,
+        // on the next tick at the latest
+        threads
+            .into_iter()
+            .filter(|thread| thread.tid() != tid)
+            .for_each(wakeup_thread);
+        let result = loop {
+            let result = self
+                .thread_exit
+                .wait_killable_if(|| self.inner_exclusive_access().thread_count() > 1, None);
+            match result {
+                WaitResult::NotBlocked => break Ok(()),
+                WaitResult::Interrupted => break Err(SysError::EAGAIN),
+                WaitResult::Woken | WaitResult::TimedOut => {},
+            }
+        };
+        self.inner_exclusive_access().exec_tid = None;
+        result
+    }
+
+    /// Have the parent wait in `wait_vfork` for this child forked by vfork.
+    pub fn start_vfork(&self) {
+        self.inner_exclusive_access().in_vfork = true;
+    }
+
+    /// Block the calling parent until this child forked by vfork calls execve or exits, or until
+    /// the parent is killed. Its memory is copied rather than borrowed, unlike Linux, which only
+    /// shows if the child writes what the parent reads afterwards.
+    pub fn wait_vfork(&self) {
+        loop {
+            match self
+                .vfork_done
+                .wait_killable_if(|| self.inner_exclusive_access().in_vfork, None)
+            {
+                WaitResult::NotBlocked | WaitResult::Interrupted => return,
+                WaitResult::Woken | WaitResult::TimedOut => {},
+            }
+        }
+    }
+
+    /// Let the parent waiting in `wait_vfork` go on, if there's one.
+    fn end_vfork(&self) {
+        if mem::take(&mut self.inner_exclusive_access().in_vfork) {
+            self.vfork_done.wake_all();
+        }
+    }
+
+    /// Called when a thread has left the process, which execve may be waiting for.
+    pub fn thread_exited(&self) {
+        self.thread_exit.wake_all();
+    }
+
+    /// Duplicate the process with the calling thread, which becomes the only thread of the child.
+    /// The child returns 0 from the syscall, on `stack` if it's not 0. Return the child and its
+    /// thread, which keeps the tid of the caller, it's up to the caller to start it.
+    pub fn fork(self: &Arc<Self>, stack: usize) -> SysResult<(Arc<Self>, Arc<ThreadControlBlock>)> {
+        self.check_nproc()?;
+        let thread = current_thread();
+        let thread_inner = thread.inner_exclusive_access();
+        let res = thread_inner.res();
+        let (res_tid, ustack_top, trap_cx_bottom) = (res.tid(), res.ustack_top(), res.trap_cx_user_va());
+        let user_stack_base = thread_inner.get_user_stack_base();
+        let sched_entity = thread_inner.sched_entity().fork();
+        let sig_mask = thread_inner.sig_mask();
+        drop(thread_inner);
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existed_user(
+            &mut parent_inner.memory,
+            VirtAddr::from(ustack_top).floor(),
+            VirtAddr::from(trap_cx_bottom).floor(),
+        )?;
+        let child = Arc::new(Self {
+            pid: pid_alloc(),
+            child_exit: Arc::new(WaitQueue::new()),
+            continued: Arc::new(WaitQueue::new()),
+            thread_exit: Arc::new(WaitQueue::new()),
+            vfork_done: Arc::new(WaitQueue::new()),
+            inner: Mutex::new(ProcessControlBlockInner {
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                status: ProcessStatus::Normal,
+                exit_code: 0,
+                exiting: false,
+                exec_tid: None,
+                in_vfork: false,
+                stopped: false,
+                job_status: None,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                has_console: parent_inner.has_console,
+                threads: Vec::new(),
+                // the only thread keeps the user resources of the caller, which are copied
+                tid_allocator: Mutex::new(QueueAllocator::new_first(res_tid)),
+                memory: memory_set,
+                // pending signals are not inherited
+                sig_actions: parent_inner.sig_actions,
+                sig_pending: SigPending::new(),
+                rlimits: parent_inner.rlimits,
+                cpu_ticks: 0,
+            }),
+        });
+        drop(parent_inner);
+
+        let child_thread = Arc::new(ThreadControlBlock::new(child.clone(), user_stack_base, false)?);
+        let mut child_thread_inner = child_thread.inner_exclusive_access();
+        *child_thread_inner.sched_entity_mut() = sched_entity;
+        child_thread_inner.set_sig_mask(sig_mask);
+        let trap_cx = child_thread_inner.get_trap_cx();
+        *trap_cx = *current_trap_cx();
+        trap_cx.x[10] = 0;
+        if stack != 0 {
+            trap_cx.x[2] = stack;
+        }
+        drop(child_thread_inner);
+
+        child.insert_thread(child_thread.clone());
+        self.inner_exclusive_access().children.push(child.clone());
+        PROCESS_MANAGER.add_process(child.pid(), &child);
+        Ok((child, child_thread))
+    }
+
+    /// Turn the process into a zombie after its last thread has exited.
+    /// The user memory is released at once, the children are handed over to initproc, and the
+    /// parent is sent SIGCHLD.
+    pub fn exit(self: &Arc<Self>, exit_code: i32) {
+        let initproc = INITPROC.get().unwrap();
+        assert!(!Arc::ptr_eq(self, initproc), "initproc exited with code {exit_code}");
+        let mut inner = self.inner_exclusive_access();
+        inner.status = ProcessStatus::Zombie;
+        if !inner.exiting {
+            inner.exit_code = (exit_code & 0xff) << 8;
+        }
+        let wstatus = inner.exit_code;
+        inner.memory.recycle_data_pages();
+        let children = mem::take(&mut inner.children);
+        let parent = inner.parent.as_ref().and_then(Weak::upgrade);
+        drop(inner);
+        self.end_vfork();
+
+        // initproc is an ancestor, which wait4 locks before us, so ours must be released here
+        let mut has_zombie = false;
+        for child in children.iter() {
+            let mut child_inner = child.inner_exclusive_access();
+            child_inner.parent = Some(Arc::downgrade(initproc));
+            has_zombie |= child_inner.status == ProcessStatus::Zombie;
+        }
+        initproc.inner_exclusive_access().children.extend(children);
+        if has_zombie {
+            initproc.child_exit.wake_all();
+        }
+        if let Some(parent) = parent {
+            send_signal(&parent, SigInfo::child_exit(self.pid(), wstatus));
+            parent.child_exit.wake_all();
+        }
+    }
+
+    /// Make all threads exit with `exit_code`, the calling thread has to exit afterwards.
+    /// Threads on other harts find out when they trap into the kernel, on the next tick at the
+    /// latest.
+    pub fn exit_group(&self, exit_code: i32) {
+        self.terminate((exit_code & 0xff) << 8);
+    }
+
+    /// Make all threads exit as if killed by `signo`, with a core dump if `core_dumped`.
+    /// No core file is written, but the parent is told there's one unless `RLIMIT_CORE` is 0.
+    pub fn exit_group_by_signal(&self, signo: usize, core_dumped: bool) {
+        let core_dumped = match core_dumped && self.rlimit(RLIMIT_CORE).cur > 0 {
+            true => 0x80,
+            false => 0,
+        };
+        self.terminate(signo as i32 | core_dumped);
+    }
+
+    /// Make all threads exit, with `wstatus` of wait4 unless it's done already.
+    fn terminate(&self, wstatus: i32) {
+        let mut inner = self.inner_exclusive_access();
+        // a zombie keeps the status it exits with
+        if inner.exiting || inner.status != ProcessStatus::Normal {
+            return;
+        }
+        inner.exiting = true;
+        inner.exit_code = wstatus;
+        let threads = inner.threads();
+        drop(inner);
+        // blocked ones are woken up to find out, stopped ones included
+        threads.into_iter().for_each(wakeup_thread);
+    }
+
+    /// Whether the current thread, which is one of the process, has to exit: all of them do once
+    /// the process is exiting, and all but the one in execve while it replaces the program.
+    pub fn is_exiting(&self) -> bool {
+        let inner = self.inner_exclusive_access();
+        inner.exiting || inner.exec_tid.is_some_and(|tid| tid != current_thread().tid())
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.inner_exclusive_access().stopped
+    }
+
+    /// Stop the process by `signo` until SIGCONT. Each thread stops when it's about to return to
+    /// user, see `wait_continued`.
+    pub fn stop(&self, signo: usize) {
+        let mut inner = self.inner_exclusive_access();
+        if inner.stopped {
+            return;
+        }
+        inner.stopped = true;
+        inner.job_status = Some(((signo as i32) << 8) | 0x7f);
+        let parent = inner.parent.as_ref().and_then(Weak::upgrade);
+        drop(inner);
+        if let Some(parent) = parent {
+            parent.notify_child_stopped(SigInfo::child_stop(self.pid(), signo));
+        }
+    }
+
+    /// Continue the process if it's stopped.
+    pub fn cont(&self) {
+        let mut inner = self.inner_exclusive_access();
+        if !inner.stopped {
+            return;
+        }
+        inner.stopped = false;
+        inner.job_status = Some(WSTATUS_CONTINUED);
+        let parent = inner.parent.as_ref().and_then(Weak::upgrade);
+        drop(inner);
+        self.continued.wake_all();
+        if let Some(parent) = parent {
+            parent.notify_child_stopped(SigInfo::child_continue(self.pid()));
+        }
+    }
+
+    /// Block the current thread while the process is stopped, unless it's killed.
+    pub fn wait_continued(&self) {
+        self.continued.wait_killable_if(|| self.is_stopped(), None);
+    }
+
+    /// Tell about a child being stopped or continued by SIGCHLD, unless it's asked not to by
+    /// `SA_NOCLDSTOP`.
+    fn notify_child_stopped(self: &Arc<Self>, info: SigInfo) {
+        let action = self.inner_exclusive_access().sig_actions[SIGCHLD - 1];
+        if !action.flags.contains(SigActionFlags::NOCLDSTOP) {
+            send_signal(self, info);
+        }
+        self.child_exit.wake_all();
+    }
+
+    /// Block until a child exits, or stops or continues if `options` asks, which wait4 should
+    /// look for then. Return at once if there's already one, see `wait_child` for `pid`.
+    pub fn wait_child_change(&self, pid: isize, options: WaitOptions) -> WaitResult {
+        self.child_exit.wait_if(|| !self.has_child_change(pid, options), None)
+    }
+
+    fn has_child_change(&self, pid: isize, options: WaitOptions) -> bool {
+        let inner = self.inner_exclusive_access();
+        inner
+            .children
+            .iter()
+            .any(|child| child.is_waited_by(pid, inner.pgid) && child.change(options, false).is_some())
+    }
+
+    /// Whether wait4 of `pid` by a parent in group `pgid` looks for this child: any child for -1,
+    /// the ones in the same group for 0, the ones in group `-pid` for others below 0.
+    fn is_waited_by(&self, pid: isize, pgid: usize) -> bool {
+        match pid {
+            -1 => true,
+            0 => self.pgid() == pgid,
+            ..0 => self.pgid() == pid.unsigned_abs(),
+            pid => self.pid() == pid as usize,
+        }
+    }
+
+    /// `wstatus` of wait4 if the process has exited, or stopped or continued and `options` asks
+    /// for it, and whether it has exited, both as seen at once. A stop or continue is reported
+    /// once if `take`.
+    fn change(&self, options: WaitOptions, take: bool) -> Option<(i32, bool)> {
+        let mut inner = self.inner_exclusive_access();
+        if inner.status == ProcessStatus::Zombie {
+            return Some((inner.exit_code, true));
+        }
+        let wstatus = inner.job_status?;
+        let wanted = match wstatus {
+            WSTATUS_CONTINUED => WaitOptions::CONTINUED,
+            _ => WaitOptions::UNTRACED,
+        };
+        if !options.contains(wanted) {
+            return None;
+        }
+        if take {
+            inner.job_status = None;
+        }
+        Some((wstatus, false))
+    }
+
+    /// Reap a zombie child, or take a stop or continue of a child if `options` asks, see
+    /// `is_waited_by` for `pid`. Return its pid and `wstatus`, `None` if there's no such change
+    /// yet.
+    pub fn wait_child(&self, pid: isize, options: WaitOptions) -> SysResult<Option<(usize, i32)>> {
+        let mut inner = self.inner_exclusive_access();
+        let pgid = inner.pgid;
+        if !inner.children.iter().any(|child| child.is_waited_by(pid, pgid)) {
+            return Err(SysError::ECHILD);
+        }
+        let Some((idx, (wstatus, zombie))) =
+            inner
+                .children
+                .iter()
+                .enumerate()
+                .find_map(|(idx, child)| match child.is_waited_by(pid, pgid) {
+                    true => child.change(options, true).map(|change| (idx, change)),
+                    false => None,
+                })
+        else {
+            return Ok(None);
+        };
+        let child = inner.children[idx].clone();
+        // a child exiting now is reaped by the next wait, after its stop or continue is reported
+        if zombie {
+            inner.children.remove(idx);
+            drop(inner);
+            PROCESS_MANAGER.remove_process(child.pid());
+        }
+        Ok(Some((child.pid(), wstatus)))
+    }
+
+    pub fn pgid(&self) -> usize {
+        self.inner_exclusive_access().pgid
+    }
+
+    /// Whether `process` is this one or one of its descendants.
+    pub fn is_ancestor_of(&self, process: &Arc<Self>) -> bool {
+        let mut process = Some(process.clone());
+        while let Some(current) = process {
+            if ptr::eq(current.as_ref(), self) {
+                return true;
+            }
+            process = current.inner_exclusive_access().parent.as_ref().and_then(Weak::upgrade);
+        }
+        false
+    }
+
+    pub fn sid(&self) -> usize {
+        self.inner_exclusive_access().sid
+    }
+
+    /// Move process `pid`, which is the caller or one of its children, to group `pgid` in the same
+    /// session. It leads a new group if `pgid` is its pid. 0 means the pid of the caller for `pid`
+    /// and the pid of the one to move for `pgid`.
+    pub fn setpgid(self: &Arc<Self>, pid: usize, pgid: usize) -> SysResult<()> {
+        let target = match pid == 0 || pid == self.pid() {
+            true => self.clone(),
+            false => self
+                .inner_exclusive_access()
+                .children
+                .iter()
+                .find(|child| child.pid() == pid)
+                .cloned()
+                .ok_or(SysError::ESRCH)?,
+        };
+        let pgid = match pgid {
+            0 => target.pid(),
+            pgid => pgid,
+        };
+        let sid = self.sid();
+        // todo: EACCES for a child which has called execve
+        if target.sid() != sid || target.pid() == sid {
+            return Err(SysError::EPERM);
+        }
+        if pgid != target.pid()
+            && !PROCESS_MANAGER
+                .process_group(pgid)
+                .iter()
+                .any(|process| process.sid() == sid)
+        {
+            return Err(SysError::EPERM);
+        }
+        target.inner_exclusive_access().pgid = pgid;
+        Ok(())
+    }
+
+    /// Make the process the leader of a new session and a new group in it, which has no
+    /// controlling terminal. Return the new sid.
+    pub fn setsid(&self) -> SysResult<usize> {
+        let pid = self.pid();
+        // the group would be split across sessions
+        if !PROCESS_MANAGER.process_group(pid).is_empty() {
+            return Err(SysError::EPERM);
+        }
+        let mut inner = self.inner_exclusive_access();
+        inner.sid = pid;
+        inner.pgid = pid;
+        inner.has_console = false;
+        Ok(pid)
+    }
+
+    /// The foreground group of the controlling terminal.
+    pub fn tcgetpgrp(&self) -> SysResult<usize> {
+        match self.inner_exclusive_access().has_console {
+            true => Ok(console_foreground()),
+            false => Err(SysError::ENOTTY),
+        }
     }
 
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8]) {}
+    /// Make group `pgid` of the same session the foreground group of the controlling terminal.
+    pub fn tcsetpgrp(self: &Arc<Self>, pgid: usize) -> SysResult<()> {
+        if !self.inner_exclusive_access().has_console {
+            return Err(SysError::ENOTTY);
+        }
+        self.check_foreground(SIGTTOU)?;
+        let sid = self.sid();
+        if !PROCESS_MANAGER
+            .process_group(pgid)
+            .iter()
+            .any(|process| process.sid() == sid)
+        {
+            return Err(SysError::EPERM);
+        }
+        CONSOLE_FOREGROUND.store(pgid, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Check whether the process may use the console like a foreground job, e.g. read from it.
+    /// A background group is sent `signo`, SIGTTIN or SIGTTOU, and the syscall is restarted once
+    /// it's continued. If the signal is blocked or ignored, only SIGTTOU lets it go on, and EIO is
+    /// returned otherwise.
+    pub fn check_foreground(&self, signo: usize) -> SysResult<()> {
+        let inner = self.inner_exclusive_access();
+        if !inner.has_console || inner.pgid == console_foreground() {
+            return Ok(());
+        }
+        let pgid = inner.pgid;
+        drop(inner);
+        if is_blocked_or_ignored(signo) {
+            return match signo {
+                SIGTTOU => Ok(()),
+                _ => Err(SysError::EIO),
+            };
+        }
+        send_signal_to_group(pgid, SigInfo::kernel(signo))?;
+        Err(SysError::ERESTARTSYS)
+    }
+
+    pub fn rlimit(&self, resource: usize) -> RLimit {
+        self.inner_exclusive_access().rlimits[resource]
+    }
+
+    /// Set the limit of `resource` and return the old one. The hard limit can't be raised, as
+    /// it takes a privilege there's no way to have yet.
+    pub fn set_rlimit(&self, resource: usize, rlimit: RLimit) -> SysResult<RLimit> {
+        if rlimit.cur > rlimit.max {
+            return Err(SysError::EINVAL);
+        }
+        let mut inner = self.inner_exclusive_access();
+        let old = inner.rlimits[resource];
+        if rlimit.max > old.max {
+            return Err(SysError::EPERM);
+        }
+        inner.rlimits[resource] = rlimit;
+        Ok(old)
+    }
+
+    /// EAGAIN if there are `RLIMIT_NPROC` threads already, so that no more can be created.
+    /// Linux counts the ones of the user, but there are no users, so all of them count: anything
+    /// narrower, like a session, could be left with `setsid` for a fresh count.
+    fn check_nproc(&self) -> SysResult<()> {
+        match PROCESS_MANAGER.thread_count() < self.rlimit(RLIMIT_NPROC).cur {
+            true => Ok(()),
+            false => Err(SysError::EAGAIN),
+        }
+    }
+
+    /// Charge a tick in user mode to the process. Like Linux, it's sent SIGXCPU each second over
+    /// the soft limit of `RLIMIT_CPU`, and killed at the hard one.
+    pub fn account_tick(self: &Arc<Self>) {
+        let mut inner = self.inner_exclusive_access();
+        inner.cpu_ticks += 1;
+        if inner.cpu_ticks % TICKS_PER_SEC != 0 {
+            return;
+        }
+        let secs = inner.cpu_ticks / TICKS_PER_SEC;
+        let limit = inner.rlimits[RLIMIT_CPU];
+        drop(inner);
+        if secs >= limit.max {
+            send_signal(self, SigInfo::kernel(SIGKILL));
+        } else if secs >= limit.cur {
+            send_signal(self, SigInfo::kernel(SIGXCPU));
+        }
+    }
+
+    pub fn pid(&self) -> usize {
+        self.pid.0
+    }
+
+    /// Whether this is initproc, which the kernel can't go on without.
+    pub fn is_initproc(self: &Arc<Self>) -> bool {
+        INITPROC.get().is_some_and(|initproc| Arc::ptr_eq(self, initproc))
+    }
+
+    /// Pid of the parent, 0 if there's none like initproc.
+    pub fn ppid(&self) -> usize {
+        self.inner_exclusive_access()
+            .parent
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map_or(0, |parent| parent.pid())
+    }
+
+    pub fn get_user_token(&self) -> usize {
+        self.inner_exclusive_access().memory.token()
+    }
 }
 
 impl ProcessControlBlockInner {
     pub fn get_thread(&self, tid: usize) -> Arc<ThreadControlBlock> {
         self.threads[tid].as_ref().unwrap().clone()
     }
+
+    /// All threads of the process, to be used without holding the lock.
+    pub fn threads(&self) -> Vec<Arc<ThreadControlBlock>> {
+        self.threads.iter().flatten().cloned().collect()
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.iter().flatten().count()
+    }
+
+    /// ENOMEM if mapping `len` more bytes would take the address space over `RLIMIT_AS`.
+    pub fn check_address_space(&self, len: usize) -> SysResult<()> {
+        match self.memory.user_size().saturating_add(len) <= self.rlimits[RLIMIT_AS].cur {
+            true => Ok(()),
+            false => Err(SysError::ENOMEM),
+        }
+    }
+
+    /// Resolve a fault of the current process at `va` for `access`, taken by a thread at user
+    /// `sp`. One below the stack of thread 0 and near `sp` grows it within `RLIMIT_STACK` and
+    /// `RLIMIT_AS`, see `MemorySet::grow_stack`.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission, sp: usize) -> SysResult<()> {
+        if self.memory.find_area(va.floor()).is_none() {
+            let max_growth = self.rlimits[RLIMIT_AS].cur.saturating_sub(self.memory.user_size());
+            self.memory
+                .grow_stack(va.floor(), sp, self.rlimits[RLIMIT_STACK].cur, max_growth)?;
+        }
+        self.memory.handle_page_fault(va, access)
+    }
+
+    /// Remove a thread from the process, the caller should drop it without holding the lock.
+    pub fn remove_thread(&mut self, tid: usize) -> Option<Arc<ThreadControlBlock>> {
+        self.threads.get_mut(tid).and_then(Option::take)
+    }
 }
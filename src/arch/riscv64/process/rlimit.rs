@@ -0,0 +1,47 @@
+//! Resource limits, see `getrlimit(2)`.
+//!
+//! Every resource of Linux has a slot, but only the ones below are enforced. `RLIMIT_NOFILE` is
+//! kept for when there's a fd table, since no file can be opened yet. `RLIMIT_STACK` only limits
+//! the stack of thread 0, as the others don't grow.
+
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// Threads which may exist at the same time. Linux gives about this much for 128 MiB of memory
+/// with our kernel stacks, so that they can't use up the memory or the pids.
+const DEFAULT_NPROC: usize = 512;
+
+/// `struct rlimit`, which is also `struct rlimit64` on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RLimit {
+    /// the soft limit, which is enforced
+    pub cur: usize,
+    /// the ceiling of the soft limit
+    pub max: usize,
+}
+
+impl RLimit {
+    pub const INFINITY: Self = Self::new(RLIM_INFINITY, RLIM_INFINITY);
+
+    pub const fn new(cur: usize, max: usize) -> Self {
+        Self { cur, max }
+    }
+}
+
+/// Limits of initproc, which the other processes inherit, like the defaults of Linux.
+pub const INIT_RLIMITS: [RLimit; RLIM_NLIMITS] = {
+    let mut rlimits = [RLimit::INFINITY; RLIM_NLIMITS];
+    rlimits[RLIMIT_STACK] = RLimit::new(8 << 20, RLIM_INFINITY);
+    rlimits[RLIMIT_CORE] = RLimit::new(0, RLIM_INFINITY);
+    rlimits[RLIMIT_NPROC] = RLimit::new(DEFAULT_NPROC, DEFAULT_NPROC);
+    rlimits[RLIMIT_NOFILE] = RLimit::new(1024, 4096);
+    rlimits
+};
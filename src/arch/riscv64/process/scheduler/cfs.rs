@@ -0,0 +1,111 @@
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+
+use super::{NICE_MIN, SchedEntity, Scheduler, can_run_on};
+use crate::arch::{config::CLOCK_FREQ, process::ThreadControlBlock, timer::get_time};
+
+const NICE_0_WEIGHT: usize = 1024;
+
+/// Weight of each nice value, from -20 to 19, the same as Linux.
+/// Each step of nice changes the share of cpu time by about 10%.
+#[rustfmt::skip]
+const SCHED_PRIO_TO_WEIGHT: [usize; 40] = [
+    88761, 71755, 56483, 46273, 36291, // -20
+    29154, 23254, 18705, 14949, 11916, // -15
+    9548, 7620, 6100, 4904, 3906, // -10
+    3121, 2501, 1991, 1586, 1277, // -5
+    1024, 820, 655, 526, 423, // 0
+    335, 272, 215, 172, 137, // 5
+    110, 87, 70, 56, 45, // 10
+    36, 29, 23, 18, 15, // 15
+];
+
+/// The running thread is preempted once its vruntime is this far ahead of the leftmost one.
+const WAKEUP_GRANULARITY: usize = CLOCK_FREQ / 1000;
+
+/// How far a woken thread may be placed behind `min_vruntime`, so that sleepers get a bit of
+/// priority without being able to starve the others.
+const SLEEPER_CREDIT: usize = CLOCK_FREQ / 1000 * 3;
+
+/// Completely fair scheduler.
+/// Ready threads are ordered by vruntime, the cpu time they used weighted by their nice.
+pub struct CfsScheduler {
+    /// keyed by (vruntime, address of the thread), so that equal vruntimes don't collide
+    ready: BTreeMap<(usize, usize), Arc<ThreadControlBlock>>,
+    /// vruntime of the most recently picked thread, never decreases
+    min_vruntime: usize,
+}
+
+impl CfsScheduler {
+    pub const fn new() -> Self {
+        Self {
+            ready: BTreeMap::new(),
+            min_vruntime: 0,
+        }
+    }
+}
+
+fn weight(nice: i32) -> usize {
+    SCHED_PRIO_TO_WEIGHT[(nice - NICE_MIN) as usize]
+}
+
+/// Charge the cpu time since `exec_start` to `se`.
+fn update_curr(se: &mut SchedEntity) {
+    if se.exec_start == 0 {
+        return;
+    }
+    let now = get_time();
+    se.vruntime += (now - se.exec_start) * NICE_0_WEIGHT / weight(se.effective_nice());
+    se.exec_start = now;
+}
+
+impl Scheduler for CfsScheduler {
+    fn enqueue(&mut self, thread: Arc<ThreadControlBlock>) {
+        let key = {
+            let mut thread_inner = thread.inner_exclusive_access();
+            let se = thread_inner.sched_entity_mut();
+            se.vruntime = se.vruntime.max(self.min_vruntime.saturating_sub(SLEEPER_CREDIT));
+            (se.vruntime, Arc::as_ptr(&thread) as usize)
+        };
+        self.ready.insert(key, thread);
+    }
+
+    fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>> {
+        let (&key, _) = self.ready.iter().find(|(_, thread)| can_run_on(thread, hart_id))?;
+        let thread = self.ready.remove(&key)?;
+        self.min_vruntime = self.min_vruntime.max(key.0);
+        Some(thread)
+    }
+
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool {
+        let Some(&key) = self
+            .ready
+            .iter()
+            .find(|(_, t)| Arc::ptr_eq(t, thread))
+            .map(|(key, _)| key)
+        else {
+            return false;
+        };
+        self.ready.remove(&key);
+        true
+    }
+
+    fn set_next(&mut self, thread: &Arc<ThreadControlBlock>) {
+        thread.inner_exclusive_access().sched_entity_mut().exec_start = get_time();
+    }
+
+    fn put_prev(&mut self, thread: &Arc<ThreadControlBlock>) {
+        let mut thread_inner = thread.inner_exclusive_access();
+        let se = thread_inner.sched_entity_mut();
+        update_curr(se);
+        se.exec_start = 0;
+    }
+
+    fn tick(&mut self, current: &Arc<ThreadControlBlock>) -> bool {
+        let mut current_inner = current.inner_exclusive_access();
+        let se = current_inner.sched_entity_mut();
+        update_curr(se);
+        self.ready
+            .first_key_value()
+            .is_some_and(|(&(leftmost, _), _)| se.vruntime > leftmost + WAKEUP_GRANULARITY)
+    }
+}
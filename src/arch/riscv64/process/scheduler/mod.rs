@@ -0,0 +1,442 @@
+//! Per-hart scheduling.
+//!
+//! Every hart has a `RunQueue` in its `CpuContext`, which delegates the policy to a `Scheduler`.
+//! The policy is chosen at build time by one of the cargo features:
+//! - `sched-cfs`: `CfsScheduler`, fair share of cpu time weighted by nice.
+//! - `sched-prio`: `PrioScheduler`, strict static priorities given by nice.
+//! - `sched-rr`(default): `RrScheduler`, round-robin ignoring nice.
+//!
+//! If more than one is enabled, the first one in the list above wins.
+//!
+//! Threads with `SCHED_FIFO` or `SCHED_RR`, or boosted by priority inheritance, are real-time.
+//! They are kept out of the `Scheduler` and always run before normal threads.
+
+#[cfg(feature = "sched-cfs")]
+mod cfs;
+#[cfg(all(feature = "sched-prio", not(feature = "sched-cfs")))]
+mod prio;
+#[cfg(not(any(feature = "sched-cfs", feature = "sched-prio")))]
+mod rr;
+mod rt;
+
+use alloc::sync::Arc;
+
+#[cfg(feature = "sched-cfs")]
+use self::cfs::CfsScheduler as SchedulerImpl;
+#[cfg(all(feature = "sched-prio", not(feature = "sched-cfs")))]
+use self::prio::PrioScheduler as SchedulerImpl;
+#[cfg(not(any(feature = "sched-cfs", feature = "sched-prio")))]
+use self::rr::RrScheduler as SchedulerImpl;
+use self::rt::RtQueue;
+use super::{
+    context::ThreadContext,
+    current_thread,
+    switch::__switch,
+    tcb::{ThreadControlBlock, ThreadStatus},
+    tty::poll_console,
+    wait_queue::check_timers,
+};
+use crate::arch::{
+    cpu::{self, local_cpu_context},
+    mm::activate_kernel_space,
+    sbi, system,
+    trap::{disable_kernel_interrupt, enable_kernel_interrupt},
+};
+
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+/// `sched_priority` of real-time policies is in [1, `RT_PRIORITY_MAX`]
+pub const RT_PRIORITY_MAX: u32 = 99;
+/// Kernel priorities below this are real-time, the same as Linux.
+const MAX_RT_PRIO: i32 = 100;
+const DEFAULT_PRIO: i32 = 120;
+/// Ticks a `SCHED_RR` thread runs before giving way to others of the same priority.
+const RR_TIMESLICE: usize = 10;
+
+/// A scheduling policy managing the ready normal threads of a hart.
+/// Real-time threads are handled by the run queue itself and never reach it.
+///
+/// Implementations read the `SchedEntity` of threads, so the lock of a run queue must be taken
+/// before the inner lock of any thread.
+pub trait Scheduler {
+    /// Add a ready thread.
+    fn enqueue(&mut self, thread: Arc<ThreadControlBlock>);
+
+    /// Remove the thread to run next on `hart_id`, skipping the ones which may not run there.
+    fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>>;
+
+    /// Remove a ready thread, return whether it was found.
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool;
+
+    /// Called when `thread` is switched to.
+    fn set_next(&mut self, _thread: &Arc<ThreadControlBlock>) {}
+
+    /// Called when `thread` leaves the hart, whether it's going to be ready, blocked or exited.
+    fn put_prev(&mut self, _thread: &Arc<ThreadControlBlock>) {}
+
+    /// Called on each timer tick while `current` is running, return whether to preempt it.
+    fn tick(&mut self, current: &Arc<ThreadControlBlock>) -> bool;
+}
+
+/// Linux scheduling policies, see `sched_setscheduler(2)`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SchedPolicy {
+    Normal = 0,
+    Fifo = 1,
+    Rr = 2,
+    Batch = 3,
+    Idle = 5,
+}
+
+impl SchedPolicy {
+    pub fn is_realtime(self) -> bool {
+        matches!(self, Self::Fifo | Self::Rr)
+    }
+}
+
+impl TryFrom<usize> for SchedPolicy {
+    type Error = ();
+
+    fn try_from(policy: usize) -> Result<Self, Self::Error> {
+        match policy {
+            0 => Ok(Self::Normal),
+            1 => Ok(Self::Fifo),
+            2 => Ok(Self::Rr),
+            3 => Ok(Self::Batch),
+            5 => Ok(Self::Idle),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Scheduling state of a thread, shared by all policies.
+///
+/// Like Linux, a kernel priority `prio` orders all threads, lower is more favorable:
+/// [0, 99) for real-time threads and [100, 140) for normal threads by nice.
+pub struct SchedEntity {
+    pub policy: SchedPolicy,
+    /// in [`NICE_MIN`, `NICE_MAX`], lower is more favorable
+    pub nice: i32,
+    /// `sched_priority` of `sched_param`, only meaningful for real-time policies
+    pub rt_priority: u32,
+    /// priority lent by threads blocked on a lock held by this thread, see `pi_boost`
+    pi_prio: Option<i32>,
+    /// harts the thread may run on, a bit for each hart id
+    pub affinity: usize,
+    /// ticks left before a `SCHED_RR` thread gives way
+    time_slice: usize,
+    /// weighted cpu time in timer cycles, used by cfs
+    pub vruntime: usize,
+    /// when the thread got on the hart, 0 if it's not running
+    pub exec_start: usize,
+}
+
+impl SchedEntity {
+    pub const fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            nice: 0,
+            rt_priority: 0,
+            pi_prio: None,
+            affinity: usize::MAX,
+            time_slice: RR_TIMESLICE,
+            vruntime: 0,
+            exec_start: 0,
+        }
+    }
+
+    /// Entity of a forked thread: the policy, nice and affinity are inherited, a lent priority
+    /// isn't.
+    pub fn fork(&self) -> Self {
+        Self {
+            policy: self.policy,
+            nice: self.nice,
+            rt_priority: self.rt_priority,
+            affinity: self.affinity,
+            // start where the parent is, or the child would run until it catches up with it
+            vruntime: self.vruntime,
+            ..Self::new()
+        }
+    }
+
+    /// Priority given by the policy of the thread itself.
+    fn normal_prio(&self) -> i32 {
+        if self.policy.is_realtime() {
+            MAX_RT_PRIO - 1 - self.rt_priority as i32
+        } else {
+            DEFAULT_PRIO + self.nice
+        }
+    }
+
+    /// Priority the thread is scheduled with, taking inherited priority into account.
+    pub fn prio(&self) -> i32 {
+        let prio = self.normal_prio();
+        self.pi_prio.map_or(prio, |pi_prio| prio.min(pi_prio))
+    }
+
+    fn is_realtime(&self) -> bool {
+        self.prio() < MAX_RT_PRIO
+    }
+
+    pub fn can_run_on(&self, hart_id: usize) -> bool {
+        self.affinity & (1 << hart_id) != 0
+    }
+
+    /// Nice used by the policy of normal threads, which may be lent by a waiter as well.
+    pub fn effective_nice(&self) -> i32 {
+        (self.prio() - DEFAULT_PRIO).max(NICE_MIN)
+    }
+}
+
+/// Ready threads of a hart.
+/// Real-time threads always run before normal ones, which are left to the `Scheduler`.
+pub struct RunQueue {
+    rt: RtQueue,
+    scheduler: SchedulerImpl,
+}
+
+impl RunQueue {
+    pub const fn new() -> Self {
+        Self {
+            rt: RtQueue::new(),
+            scheduler: SchedulerImpl::new(),
+        }
+    }
+
+    fn enqueue(&mut self, thread: Arc<ThreadControlBlock>) {
+        let prio = thread.inner_exclusive_access().sched_entity().prio();
+        if prio < MAX_RT_PRIO {
+            self.rt.enqueue(prio, thread);
+        } else {
+            self.scheduler.enqueue(thread);
+        }
+    }
+
+    fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>> {
+        self.rt.pick_next(hart_id).or_else(|| self.scheduler.pick_next(hart_id))
+    }
+
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool {
+        self.rt.remove(thread) || self.scheduler.remove(thread)
+    }
+
+    fn tick(&mut self, current: &Arc<ThreadControlBlock>) -> bool {
+        let mut current_inner = current.inner_exclusive_access();
+        let se = current_inner.sched_entity_mut();
+        if !se.is_realtime() {
+            drop(current_inner);
+            return !self.rt.is_empty() || self.scheduler.tick(current);
+        }
+        let prio = se.prio();
+        let waiting = self.rt.highest_prio().unwrap_or(MAX_RT_PRIO);
+        if waiting < prio {
+            return true;
+        }
+        // a fifo thread runs until it blocks or yields
+        if se.policy != SchedPolicy::Rr {
+            return false;
+        }
+        se.time_slice -= 1;
+        if se.time_slice > 0 {
+            return false;
+        }
+        se.time_slice = RR_TIMESLICE;
+        waiting == prio
+    }
+}
+
+/// Whether `thread` may run on `hart_id`, which is asked with the run queue locked.
+fn can_run_on(thread: &Arc<ThreadControlBlock>, hart_id: usize) -> bool {
+    thread.inner_exclusive_access().sched_entity().can_run_on(hart_id)
+}
+
+/// Put a ready thread into the run queue of this hart, or of a hart it may run on if it may not
+/// run here.
+/// A real-time thread preempts the current thread of that hart if that one is less favorable,
+/// which another hart is interrupted to find out, see `need_resched`. Normal threads wait for the
+/// next tick.
+pub fn add_thread(thread: Arc<ThreadControlBlock>) {
+    let cpu_context = local_cpu_context();
+    let (prio, affinity) = {
+        let thread_inner = thread.inner_exclusive_access();
+        let se = thread_inner.sched_entity();
+        (se.prio(), se.affinity)
+    };
+    let target = match affinity & (1 << cpu_context.hart_id()) {
+        0 => cpu::cpu_contexts()
+            .find(|target| affinity & (1 << target.hart_id()) != 0)
+            .unwrap_or(cpu_context),
+        _ => cpu_context,
+    };
+    target.run_queue().lock().enqueue(thread);
+    if prio < MAX_RT_PRIO {
+        target.set_need_resched();
+        if target.hart_id() != cpu::hart_id() {
+            sbi::send_ipi(target.hart_id());
+        }
+    }
+}
+
+/// Whether the current thread should give up the hart to a real-time thread queued here since
+/// the last time it's asked, by this hart or another one.
+pub fn need_resched() -> bool {
+    if !local_cpu_context().take_need_resched() {
+        return false;
+    }
+    let prio = current_thread().inner_exclusive_access().sched_entity().prio();
+    let waiting = local_cpu_context().run_queue().lock().rt.highest_prio();
+    waiting.is_some_and(|waiting| waiting < prio)
+}
+
+/// Take a thread from this hart, or steal one from another hart if there's nothing to run.
+fn fetch_thread() -> Option<Arc<ThreadControlBlock>> {
+    let hart_id = cpu::hart_id();
+    if let Some(thread) = local_cpu_context().run_queue().lock().pick_next(hart_id) {
+        return Some(thread);
+    }
+    cpu::cpu_contexts().find_map(|cpu_context| cpu_context.run_queue().lock().pick_next(hart_id))
+}
+
+/// Account a timer tick to the current thread, return whether it should give up the hart.
+pub fn scheduler_tick() -> bool {
+    let current = current_thread();
+    local_cpu_context().run_queue().lock().tick(&current)
+}
+
+/// Change the scheduling parameters of `thread` with `f`.
+/// A ready thread is queued again, so that its new priority takes effect at once, and moved to
+/// another hart if it may no longer run on the one it's queued on.
+pub fn update_sched_entity(thread: &Arc<ThreadControlBlock>, f: impl FnOnce(&mut SchedEntity)) {
+    for cpu_context in cpu::cpu_contexts() {
+        let mut run_queue = cpu_context.run_queue().lock();
+        if run_queue.remove(thread) {
+            let mut thread_inner = thread.inner_exclusive_access();
+            let se = thread_inner.sched_entity_mut();
+            f(se);
+            let stays = se.can_run_on(cpu_context.hart_id());
+            drop(thread_inner);
+            match stays {
+                true => run_queue.enqueue(thread.clone()),
+                false => {
+                    drop(run_queue);
+                    add_thread(thread.clone());
+                },
+            }
+            return;
+        }
+    }
+    // running or blocked, the new priority is used when it's queued next time
+    f(thread.inner_exclusive_access().sched_entity_mut());
+}
+
+/// Lend `prio` to `owner` of a lock, which a thread with `prio` is going to block on.
+/// So a less favorable owner is not kept from releasing the lock by threads in between.
+pub fn pi_boost(owner: &Arc<ThreadControlBlock>, prio: i32) {
+    update_sched_entity(owner, |se| {
+        se.pi_prio = Some(se.pi_prio.map_or(prio, |pi_prio| pi_prio.min(prio)));
+    });
+}
+
+/// Give back the priority lent by `pi_boost` once `owner` releases a lock, except `prio` lent by
+/// threads blocked on the other locks it still holds.
+pub fn pi_restore(owner: &Arc<ThreadControlBlock>, prio: Option<i32>) {
+    update_sched_entity(owner, |se| se.pi_prio = prio);
+}
+
+/// The idle control flow of a hart, running on its boot stack.
+/// Pick a ready thread and switch to it, and take care of it after it gives up the hart.
+pub fn run_threads() -> ! {
+    loop {
+        check_timers();
+        poll_console();
+        let Some(thread) = fetch_thread() else {
+            // wait for the next interrupt with interrupts enabled, so that it gets handled
+            enable_kernel_interrupt();
+            system::halt();
+            disable_kernel_interrupt();
+            continue;
+        };
+        let next_thread_cx_ptr = {
+            let mut thread_inner = thread.inner_exclusive_access();
+            thread_inner.set_status(ThreadStatus::Running);
+            thread_inner.set_on_cpu(true);
+            thread_inner.get_thread_context_ptr() as *const ThreadContext
+        };
+        thread.process().inner_exclusive_access().memory.activate();
+        let cpu_context = local_cpu_context();
+        cpu_context.run_queue().lock().scheduler.set_next(&thread);
+        // whoever asked for it is going to be switched to from here
+        cpu_context.take_need_resched();
+        cpu_context.set_current(thread);
+        unsafe { __switch(cpu_context.idle_thread_context_ptr(), next_thread_cx_ptr) };
+
+        // the page table of the previous thread may go away with it
+        activate_kernel_space();
+        if let Some(thread) = local_cpu_context().take_current() {
+            put_prev_thread(thread);
+        }
+    }
+}
+
+/// Called on the idle control flow once `thread` has left the hart.
+fn put_prev_thread(thread: Arc<ThreadControlBlock>) {
+    local_cpu_context().run_queue().lock().scheduler.put_prev(&thread);
+    let mut thread_inner = thread.inner_exclusive_access();
+    thread_inner.set_on_cpu(false);
+    match thread_inner.get_status() {
+        // preempted or yielded
+        ThreadStatus::Running => {
+            thread_inner.set_status(ThreadStatus::Ready);
+            drop(thread_inner);
+            add_thread(thread);
+        },
+        // woken up before leaving the hart
+        ThreadStatus::Ready => {
+            drop(thread_inner);
+            add_thread(thread);
+        },
+        // the waker or the process holds it from now on
+        ThreadStatus::Blocked | ThreadStatus::Exited => {},
+    }
+}
+
+/// Switch from the current thread to the idle control flow of this hart.
+/// The caller must not hold any lock or `Arc` of the current thread.
+pub(super) fn schedule() {
+    let current_thread_cx_ptr = current_thread().inner_exclusive_access().get_thread_context_ptr();
+    let idle_thread_cx_ptr = local_cpu_context().idle_thread_context_ptr();
+    unsafe { __switch(current_thread_cx_ptr, idle_thread_cx_ptr) };
+}
+
+/// Give up the hart, the current thread stays ready to run.
+pub fn suspend_current_and_run_next() {
+    schedule();
+}
+
+/// Block the current thread until someone calls `wakeup_thread` on it.
+pub fn block_current_and_run_next() {
+    current_thread()
+        .inner_exclusive_access()
+        .set_status(ThreadStatus::Blocked);
+    schedule();
+}
+
+/// Make a blocked thread ready to run again.
+pub fn wakeup_thread(thread: Arc<ThreadControlBlock>) {
+    let mut thread_inner = thread.inner_exclusive_access();
+    if thread_inner.get_status() != ThreadStatus::Blocked {
+        return;
+    }
+    thread_inner.set_status(ThreadStatus::Ready);
+    // otherwise `put_prev_thread` will queue it once it leaves the hart
+    if !thread_inner.is_on_cpu() {
+        drop(thread_inner);
+        add_thread(thread);
+    }
+}
+
+/// Leave the hart for good, the current thread must have been marked as exited.
+pub fn exit_and_run_next() -> ! {
+    schedule();
+    unreachable!("exited thread is scheduled again");
+}
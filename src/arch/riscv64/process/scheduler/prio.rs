@@ -0,0 +1,57 @@
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    sync::Arc,
+};
+
+use super::{Scheduler, can_run_on};
+use crate::arch::process::ThreadControlBlock;
+
+/// Static priorities given by nice, or the one lent by priority inheritance.
+/// A ready thread with lower nice always runs first, threads with the same nice take turns.
+pub struct PrioScheduler {
+    ready: BTreeMap<i32, VecDeque<Arc<ThreadControlBlock>>>,
+}
+
+impl PrioScheduler {
+    pub const fn new() -> Self {
+        Self { ready: BTreeMap::new() }
+    }
+}
+
+impl Scheduler for PrioScheduler {
+    fn enqueue(&mut self, thread: Arc<ThreadControlBlock>) {
+        let nice = thread.inner_exclusive_access().sched_entity().effective_nice();
+        self.ready.entry(nice).or_default().push_back(thread);
+    }
+
+    fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>> {
+        let (nice, index) = self.ready.iter().find_map(|(&nice, queue)| {
+            let index = queue.iter().position(|thread| can_run_on(thread, hart_id))?;
+            Some((nice, index))
+        })?;
+        let queue = self.ready.get_mut(&nice).unwrap();
+        let thread = queue.remove(index);
+        if queue.is_empty() {
+            self.ready.remove(&nice);
+        }
+        thread
+    }
+
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool {
+        for (&nice, queue) in self.ready.iter_mut() {
+            if let Some(index) = queue.iter().position(|t| Arc::ptr_eq(t, thread)) {
+                queue.remove(index);
+                if queue.is_empty() {
+                    self.ready.remove(&nice);
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn tick(&mut self, current: &Arc<ThreadControlBlock>) -> bool {
+        let nice = current.inner_exclusive_access().sched_entity().effective_nice();
+        self.ready.first_key_value().is_some_and(|(&first, _)| first <= nice)
+    }
+}
@@ -0,0 +1,38 @@
+use alloc::{collections::vec_deque::VecDeque, sync::Arc};
+
+use super::{Scheduler, can_run_on};
+use crate::arch::process::ThreadControlBlock;
+
+/// Round-robin, ready threads take turns to run for a tick.
+pub struct RrScheduler {
+    ready: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl RrScheduler {
+    pub const fn new() -> Self {
+        Self { ready: VecDeque::new() }
+    }
+}
+
+impl Scheduler for RrScheduler {
+    fn enqueue(&mut self, thread: Arc<ThreadControlBlock>) {
+        self.ready.push_back(thread);
+    }
+
+    fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>> {
+        let index = self.ready.iter().position(|thread| can_run_on(thread, hart_id))?;
+        self.ready.remove(index)
+    }
+
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool {
+        let Some(index) = self.ready.iter().position(|t| Arc::ptr_eq(t, thread)) else {
+            return false;
+        };
+        self.ready.remove(index);
+        true
+    }
+
+    fn tick(&mut self, _current: &Arc<ThreadControlBlock>) -> bool {
+        !self.ready.is_empty()
+    }
+}
@@ -0,0 +1,58 @@
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    sync::Arc,
+};
+
+use super::can_run_on;
+use crate::arch::process::ThreadControlBlock;
+
+/// Ready real-time threads by kernel priority, first in first out within a priority.
+pub struct RtQueue {
+    ready: BTreeMap<i32, VecDeque<Arc<ThreadControlBlock>>>,
+}
+
+impl RtQueue {
+    pub const fn new() -> Self {
+        Self { ready: BTreeMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+
+    /// The most favorable priority of the ready threads.
+    pub fn highest_prio(&self) -> Option<i32> {
+        self.ready.first_key_value().map(|(&prio, _)| prio)
+    }
+
+    pub fn enqueue(&mut self, prio: i32, thread: Arc<ThreadControlBlock>) {
+        self.ready.entry(prio).or_default().push_back(thread);
+    }
+
+    /// Remove the most favorable thread which may run on `hart_id`.
+    pub fn pick_next(&mut self, hart_id: usize) -> Option<Arc<ThreadControlBlock>> {
+        let (prio, index) = self.ready.iter().find_map(|(&prio, queue)| {
+            let index = queue.iter().position(|thread| can_run_on(thread, hart_id))?;
+            Some((prio, index))
+        })?;
+        let queue = self.ready.get_mut(&prio).unwrap();
+        let thread = queue.remove(index);
+        if queue.is_empty() {
+            self.ready.remove(&prio);
+        }
+        thread
+    }
+
+    pub fn remove(&mut self, thread: &Arc<ThreadControlBlock>) -> bool {
+        for (&prio, queue) in self.ready.iter_mut() {
+            if let Some(index) = queue.iter().position(|t| Arc::ptr_eq(t, thread)) {
+                queue.remove(index);
+                if queue.is_empty() {
+                    self.ready.remove(&prio);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
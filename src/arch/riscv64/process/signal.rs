@@ -0,0 +1,658 @@
+//! Signals, see `signal(7)`.
+//!
+//! Actions and signals sent to the process belong to the process, masks and signals sent to a
+//! thread belong to the thread. Pending signals are acted on by `handle_signals` when a thread is
+//! about to return to user, and a handler is run by returning to it with a signal frame pushed on
+//! the user stack, which `sigreturn` restores.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::mem::{offset_of, size_of};
+
+use bitflags::bitflags;
+
+use super::{
+    current_process, current_thread, current_trap_cx, exit_current, manager::PROCESS_MANAGER, pcb::ProcessControlBlock,
+    scheduler::wakeup_thread, tcb::ThreadControlBlock,
+};
+use crate::{
+    arch::{config::SIGRETURN_TRAMPOLINE, mm::user_ptr::UserPtr, syscall::SYSCALL_RESTART_SYSCALL, timer::TimeSpec},
+    error::{SysError, SysResult},
+};
+
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGXCPU: usize = 24;
+pub const SIGXFSZ: usize = 25;
+pub const SIGWINCH: usize = 28;
+pub const SIGSYS: usize = 31;
+/// Signals from `SIGRTMIN` on are real-time ones, which are queued rather than merged.
+pub const SIGRTMIN: usize = 32;
+/// Signals are numbered from 1 to `NSIG`.
+pub const NSIG: usize = 64;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+// `si_code` of `siginfo_t`
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+pub const SI_TKILL: i32 = -6;
+pub const ILL_ILLOPC: i32 = 1;
+pub const ILL_ILLTRP: i32 = 4;
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+pub const BUS_ADRALN: i32 = 1;
+pub const TRAP_BRKPT: i32 = 1;
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+const CLD_DUMPED: i32 = 3;
+const CLD_STOPPED: i32 = 5;
+const CLD_CONTINUED: i32 = 6;
+
+/// `ss_flags` of a `stack_t` which is not in use
+const SS_DISABLE: i32 = 2;
+
+/// A set of signals, bit `signo - 1` for each.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SigSet(u64);
+
+impl SigSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn single(signo: usize) -> Self {
+        Self(1 << (signo - 1))
+    }
+
+    pub fn contains(self, signo: usize) -> bool {
+        self.0 & Self::single(signo).0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+/// SIGKILL and SIGSTOP can't be blocked, caught or ignored.
+pub const UNBLOCKABLE: SigSet = SigSet::single(SIGKILL).union(SigSet::single(SIGSTOP));
+const STOP_SIGNALS: SigSet = SigSet::single(SIGSTOP)
+    .union(SigSet::single(SIGTSTP))
+    .union(SigSet::single(SIGTTIN))
+    .union(SigSet::single(SIGTTOU));
+
+bitflags! {
+    /// `sa_flags` of `struct sigaction`
+    #[derive(Clone, Copy, Debug)]
+    pub struct SigActionFlags: usize {
+        const NOCLDSTOP = 1;
+        const NOCLDWAIT = 2;
+        const SIGINFO = 4;
+        const ONSTACK = 0x0800_0000;
+        const RESTART = 0x1000_0000;
+        const NODEFER = 0x4000_0000;
+        const RESETHAND = 0x8000_0000;
+    }
+}
+
+/// `struct sigaction` of Linux on riscv, which has no `sa_restorer`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SigAction {
+    /// `SIG_DFL`, `SIG_IGN` or the address of the handler
+    pub handler: usize,
+    pub flags: SigActionFlags,
+    /// blocked in addition while the handler runs
+    pub mask: SigSet,
+}
+
+impl SigAction {
+    pub const DEFAULT: Self = Self {
+        handler: SIG_DFL,
+        flags: SigActionFlags::empty(),
+        mask: SigSet::empty(),
+    };
+
+    /// Whether `signo` is discarded at once with this action.
+    pub fn is_ignored(&self, signo: usize) -> bool {
+        match self.handler {
+            SIG_IGN => true,
+            SIG_DFL => matches!(default_action(signo), DefaultAction::Ignore | DefaultAction::Continue),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DefaultAction {
+    Terminate,
+    CoreDump,
+    Stop,
+    /// the process is continued when it's sent, then it's ignored
+    Continue,
+    Ignore,
+}
+
+fn default_action(signo: usize) -> DefaultAction {
+    match signo {
+        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU | SIGXFSZ | SIGSYS => {
+            DefaultAction::CoreDump
+        },
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        SIGCONT => DefaultAction::Continue,
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        // e.g. SIGHUP, SIGINT, SIGKILL, SIGTERM and the real-time ones
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// A pending signal, turned into `siginfo_t` for the handler.
+#[derive(Clone, Copy, Debug)]
+pub struct SigInfo {
+    pub signo: usize,
+    pub code: i32,
+    /// the leading words of the union in `siginfo_t`, whose meaning depends on the signal
+    fields: [usize; 2],
+}
+
+impl SigInfo {
+    /// Sent by process `pid`, e.g. by kill.
+    pub fn user(signo: usize, code: i32, pid: usize) -> Self {
+        // si_pid, and si_uid of 0 in the upper half
+        Self {
+            signo,
+            code,
+            fields: [pid as u32 as usize, 0],
+        }
+    }
+
+    /// Raised by a fault at `addr`.
+    pub fn fault(signo: usize, code: i32, addr: usize) -> Self {
+        Self {
+            signo,
+            code,
+            fields: [addr, 0],
+        }
+    }
+
+    pub fn kernel(signo: usize) -> Self {
+        Self {
+            signo,
+            code: SI_KERNEL,
+            fields: [0; 2],
+        }
+    }
+
+    /// SIGCHLD about child `pid`, with the exit code or the signal as `status`.
+    fn child(code: i32, pid: usize, status: i32) -> Self {
+        Self {
+            signo: SIGCHLD,
+            code,
+            fields: [pid as u32 as usize, status as u32 as usize],
+        }
+    }
+
+    /// SIGCHLD about child `pid` which exits with `wstatus` of wait4.
+    pub fn child_exit(pid: usize, wstatus: i32) -> Self {
+        match wstatus & 0x7f {
+            0 => Self::child(CLD_EXITED, pid, (wstatus >> 8) & 0xff),
+            signo if wstatus & 0x80 != 0 => Self::child(CLD_DUMPED, pid, signo),
+            signo => Self::child(CLD_KILLED, pid, signo),
+        }
+    }
+
+    /// SIGCHLD about child `pid` which is stopped by `signo`.
+    pub fn child_stop(pid: usize, signo: usize) -> Self {
+        Self::child(CLD_STOPPED, pid, signo as i32)
+    }
+
+    /// SIGCHLD about child `pid` which is continued.
+    pub fn child_continue(pid: usize) -> Self {
+        Self::child(CLD_CONTINUED, pid, SIGCONT as i32)
+    }
+}
+
+/// Signals sent but not taken yet. A standard signal is pending at most once, while real-time
+/// ones are queued.
+pub struct SigPending {
+    infos: Vec<SigInfo>,
+}
+
+impl SigPending {
+    pub const fn new() -> Self {
+        Self { infos: Vec::new() }
+    }
+
+    pub fn set(&self) -> SigSet {
+        self.infos
+            .iter()
+            .fold(SigSet::empty(), |set, info| set.union(SigSet::single(info.signo)))
+    }
+
+    fn push(&mut self, info: SigInfo) {
+        if info.signo < SIGRTMIN && self.set().contains(info.signo) {
+            return;
+        }
+        self.infos.push(info);
+    }
+
+    /// Take the signal of the lowest number not in `mask`, the earliest one if it's queued.
+    fn take(&mut self, mask: SigSet) -> Option<SigInfo> {
+        let idx = self
+            .infos
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| !mask.contains(info.signo))
+            .min_by_key(|(idx, info)| (info.signo, *idx))
+            .map(|(idx, _)| idx)?;
+        Some(self.infos.remove(idx))
+    }
+
+    pub fn remove(&mut self, set: SigSet) {
+        self.infos.retain(|info| !set.contains(info.signo));
+    }
+}
+
+/// `siginfo_t` of Linux, whose union starts at offset 16.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LinuxSigInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: i32,
+    fields: [usize; 14],
+}
+
+impl From<SigInfo> for LinuxSigInfo {
+    fn from(info: SigInfo) -> Self {
+        let mut fields = [0; 14];
+        fields[..2].copy_from_slice(&info.fields);
+        Self {
+            signo: info.signo as i32,
+            errno: 0,
+            code: info.code,
+            _pad: 0,
+            fields,
+        }
+    }
+}
+
+/// `stack_t`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalStack {
+    sp: usize,
+    flags: i32,
+    size: usize,
+}
+
+/// `struct sigcontext` of Linux on riscv: pc and x1~x31, then the floating-point state, which
+/// isn't saved by this kernel and left zeroed.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct MContext {
+    regs: [usize; 32],
+    fpregs: [u64; 66],
+}
+
+/// `struct ucontext` of Linux on riscv
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    stack: SignalStack,
+    sigmask: SigSet,
+    /// room for a larger `sigset_t` in the future, as Linux leaves
+    _unused: [u8; 120],
+    mcontext: MContext,
+}
+
+/// `struct rt_sigframe` of Linux on riscv, pushed on the user stack to run a handler.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    info: LinuxSigInfo,
+    ucontext: UContext,
+}
+
+/// Whether `signo` sent to `process` is dropped because it's initproc without a handler for it,
+/// even SIGKILL and SIGSTOP, like `SIGNAL_UNKILLABLE` of Linux. Faults of its own are still taken.
+fn is_unkillable(process: &Arc<ProcessControlBlock>, signo: usize) -> bool {
+    process.is_initproc() && process.inner_exclusive_access().sig_actions[signo - 1].handler == SIG_DFL
+}
+
+/// Send a signal to `process`, which is taken by any thread not blocking it.
+pub fn send_signal(process: &Arc<ProcessControlBlock>, info: SigInfo) {
+    let signo = info.signo;
+    if is_unkillable(process, signo) {
+        return;
+    }
+    if signo == SIGKILL {
+        // fatal at once like Linux, the threads find out when they trap into the kernel
+        process.exit_group_by_signal(SIGKILL, false);
+        return;
+    }
+    prepare_signal(process, signo);
+    let mut inner = process.inner_exclusive_access();
+    if inner.sig_actions[signo - 1].is_ignored(signo) {
+        return;
+    }
+    inner.sig_pending.push(info);
+    let threads = inner.threads();
+    drop(inner);
+    // so that it's interrupted if it's blocked
+    if let Some(thread) = threads
+        .into_iter()
+        .find(|thread| !thread.inner_exclusive_access().sig_mask().contains(signo))
+    {
+        wakeup_thread(thread);
+    }
+}
+
+/// Send a signal to every process in group `pgid`.
+pub fn send_signal_to_group(pgid: usize, info: SigInfo) -> SysResult<()> {
+    let processes = PROCESS_MANAGER.process_group(pgid);
+    if processes.is_empty() {
+        return Err(SysError::ESRCH);
+    }
+    for process in processes.iter() {
+        send_signal(process, info);
+    }
+    Ok(())
+}
+
+/// Send a signal to `thread` only, e.g. by tgkill.
+pub fn send_signal_to_thread(thread: &Arc<ThreadControlBlock>, info: SigInfo) {
+    let process = thread.process();
+    let signo = info.signo;
+    if is_unkillable(&process, signo) {
+        return;
+    }
+    if signo == SIGKILL {
+        process.exit_group_by_signal(SIGKILL, false);
+        return;
+    }
+    prepare_signal(&process, signo);
+    if process.inner_exclusive_access().sig_actions[signo - 1].is_ignored(signo) {
+        return;
+    }
+    thread.inner_exclusive_access().sig_pending_mut().push(info);
+    wakeup_thread(thread.clone());
+}
+
+/// Stop signals and SIGCONT cancel each other out when sent, and SIGCONT continues the process
+/// at once, even if it's blocked or ignored.
+fn prepare_signal(process: &Arc<ProcessControlBlock>, signo: usize) {
+    if signo == SIGCONT {
+        discard_signals(process, STOP_SIGNALS);
+        process.cont();
+    } else if STOP_SIGNALS.contains(signo) {
+        discard_signals(process, SigSet::single(SIGCONT));
+    }
+}
+
+/// Remove `set` from the pending signals of `process` and all its threads.
+fn discard_signals(process: &Arc<ProcessControlBlock>, set: SigSet) {
+    let mut inner = process.inner_exclusive_access();
+    inner.sig_pending.remove(set);
+    let threads = inner.threads();
+    drop(inner);
+    for thread in threads {
+        thread.inner_exclusive_access().sig_pending_mut().remove(set);
+    }
+}
+
+/// Raise a signal for a fault of the current thread. It can't be blocked or ignored: the default
+/// action is taken then, like Linux.
+pub fn force_signal(info: SigInfo) {
+    let signo = info.signo;
+    let thread = current_thread();
+    let blocked = thread.inner_exclusive_access().sig_mask().contains(signo);
+    let process = thread.process();
+    let mut process_inner = process.inner_exclusive_access();
+    let action = &mut process_inner.sig_actions[signo - 1];
+    if blocked || action.handler == SIG_IGN {
+        *action = SigAction::DEFAULT;
+    }
+    drop(process_inner);
+    let mut thread_inner = thread.inner_exclusive_access();
+    let mask = thread_inner.sig_mask();
+    thread_inner.set_sig_mask(mask.difference(SigSet::single(signo)));
+    thread_inner.sig_pending_mut().push(info);
+}
+
+/// Replace the action of `signo` of `process`, return the old one.
+/// Pending ones are discarded if it's going to be ignored.
+pub fn set_sig_action(process: &Arc<ProcessControlBlock>, signo: usize, action: SigAction) -> SigAction {
+    let old = core::mem::replace(&mut process.inner_exclusive_access().sig_actions[signo - 1], action);
+    if action.is_ignored(signo) {
+        discard_signals(process, SigSet::single(signo));
+    }
+    old
+}
+
+/// Whether `signo` is blocked by the current thread or ignored by its process, so it would never
+/// be acted on.
+pub fn is_blocked_or_ignored(signo: usize) -> bool {
+    current_thread().inner_exclusive_access().sig_mask().contains(signo)
+        || current_process().inner_exclusive_access().sig_actions[signo - 1].is_ignored(signo)
+}
+
+/// Whether the current thread has a signal to take, or its process is exiting, which is taken as
+/// SIGKILL like Linux. A blocking syscall should be interrupted then.
+pub fn has_pending_signal() -> bool {
+    let thread = current_thread();
+    let process = thread.process();
+    if process.is_exiting() {
+        return true;
+    }
+    let thread_inner = thread.inner_exclusive_access();
+    let mask = thread_inner.sig_mask();
+    let pending = thread_inner.sig_pending().set();
+    drop(thread_inner);
+    let pending = pending.union(process.inner_exclusive_access().sig_pending.set());
+    !pending.difference(mask).is_empty()
+}
+
+/// Take a pending signal not blocked by the current thread, the ones sent to it first.
+fn take_signal(thread: &Arc<ThreadControlBlock>, process: &Arc<ProcessControlBlock>) -> Option<SigInfo> {
+    let mut thread_inner = thread.inner_exclusive_access();
+    let mask = thread_inner.sig_mask();
+    if let Some(info) = thread_inner.sig_pending_mut().take(mask) {
+        return Some(info);
+    }
+    drop(thread_inner);
+    process.inner_exclusive_access().sig_pending.take(mask)
+}
+
+/// Act on the pending signals of the current thread, which is about to return to user.
+/// It may never return if the process exits, or not until it's continued if it's stopped.
+///
+/// `syscall_a0` is the original a0 if it's returning from a syscall, which is restarted or made to
+/// return EINTR if it's interrupted, see `SysError::ERESTARTSYS`.
+pub fn handle_signals(mut syscall_a0: Option<usize>) {
+    loop {
+        let thread = current_thread();
+        let process = thread.process();
+        if process.is_exiting() {
+            drop(process);
+            drop(thread);
+            exit_current(0);
+        }
+        if process.is_stopped() {
+            drop(thread);
+            process.wait_continued();
+            continue;
+        }
+        let Some(info) = take_signal(&thread, &process) else {
+            // no handler runs, e.g. it's stopped and continued
+            if let Some(a0) = syscall_a0 {
+                fix_interrupted_syscall(a0, None);
+            }
+            return;
+        };
+        let signo = info.signo;
+        let action = process.inner_exclusive_access().sig_actions[signo - 1];
+        match action.handler {
+            SIG_IGN => {},
+            SIG_DFL => match default_action(signo) {
+                DefaultAction::Terminate => process.exit_group_by_signal(signo, false),
+                // there's no core file, but the parent is told that there would be
+                DefaultAction::CoreDump => process.exit_group_by_signal(signo, true),
+                DefaultAction::Stop => process.stop(signo),
+                DefaultAction::Continue | DefaultAction::Ignore => {},
+            },
+            _ => {
+                // before the registers are saved in the frame
+                if let Some(a0) = syscall_a0.take() {
+                    fix_interrupted_syscall(a0, Some(&action));
+                }
+                if run_handler(&thread, &process, info, action) {
+                    return;
+                }
+                // nowhere to push the frame, killed like Linux
+                process.exit_group_by_signal(SIGSEGV, true);
+            },
+        }
+    }
+}
+
+/// What `restart_syscall` carries on with, saved by a syscall returning `ERESTART_RESTARTBLOCK`.
+/// The time left is kept by the deadline, so it isn't slept over again.
+#[derive(Clone, Copy)]
+pub enum RestartBlock {
+    /// nanosleep until `deadline` in timer cycles
+    Nanosleep { deadline: usize, rem: UserPtr<TimeSpec> },
+    /// `FUTEX_WAIT` until `deadline` in timer cycles
+    FutexWait { uaddr: usize, val: u32, deadline: usize },
+}
+
+/// Restart the syscall interrupted by a signal, whose original a0 is `a0`, or make it return
+/// EINTR, depending on what it returns and the `action` of the handler about to run if there's
+/// one. Nothing is done if it isn't interrupted.
+fn fix_interrupted_syscall(a0: usize, action: Option<&SigAction>) {
+    let trap_cx = current_trap_cx();
+    let ret = trap_cx.x[10] as isize;
+    let restart = if ret == SysError::ERESTARTSYS.code() {
+        action.is_none_or(|action| action.flags.contains(SigActionFlags::RESTART))
+    } else if ret == SysError::ERESTARTNOINTR.code() {
+        true
+    } else if ret == SysError::ERESTARTNOHAND.code() {
+        action.is_none()
+    } else if ret == SysError::ERESTART_RESTARTBLOCK.code() {
+        if action.is_none() {
+            trap_cx.sepc -= 4;
+            trap_cx.x[17] = SYSCALL_RESTART_SYSCALL;
+            return;
+        }
+        false
+    } else {
+        return;
+    };
+    if restart {
+        // back to the ecall, with a7 and the other arguments untouched
+        trap_cx.sepc -= 4;
+        trap_cx.x[10] = a0;
+    } else {
+        trap_cx.x[10] = SysError::EINTR.code() as usize;
+    }
+}
+
+/// Make the current thread return to the handler of `info`, with a signal frame on its stack.
+/// Return false if the frame can't be pushed.
+fn run_handler(
+    thread: &Arc<ThreadControlBlock>,
+    process: &Arc<ProcessControlBlock>,
+    info: SigInfo,
+    action: SigAction,
+) -> bool {
+    let trap_cx = current_trap_cx();
+    let old_mask = thread.inner_exclusive_access().sig_mask();
+    let frame_addr = trap_cx.x[2].wrapping_sub(size_of::<SignalFrame>()) & !0xf;
+    let mut regs = trap_cx.x;
+    regs[0] = trap_cx.sepc;
+    let frame = SignalFrame {
+        info: info.into(),
+        ucontext: UContext {
+            flags: 0,
+            link: 0,
+            stack: SignalStack {
+                sp: 0,
+                flags: SS_DISABLE,
+                size: 0,
+            },
+            sigmask: old_mask,
+            _unused: [0; 120],
+            mcontext: MContext { regs, fpregs: [0; 66] },
+        },
+    };
+    if UserPtr::<SignalFrame>::new(frame_addr).write(frame).is_err() {
+        return false;
+    }
+
+    // handler(signo, &info, &ucontext), returning to the trampoline
+    trap_cx.sepc = action.handler;
+    trap_cx.x[1] = SIGRETURN_TRAMPOLINE;
+    trap_cx.x[2] = frame_addr;
+    trap_cx.x[10] = info.signo;
+    trap_cx.x[11] = frame_addr + offset_of!(SignalFrame, info);
+    trap_cx.x[12] = frame_addr + offset_of!(SignalFrame, ucontext);
+
+    let mut mask = old_mask.union(action.mask);
+    if !action.flags.contains(SigActionFlags::NODEFER) {
+        mask = mask.union(SigSet::single(info.signo));
+    }
+    thread
+        .inner_exclusive_access()
+        .set_sig_mask(mask.difference(UNBLOCKABLE));
+    if action.flags.contains(SigActionFlags::RESETHAND) {
+        process.inner_exclusive_access().sig_actions[info.signo - 1] = SigAction::DEFAULT;
+    }
+    true
+}
+
+/// Return from a handler by the trampoline: restore the registers and the mask saved in the
+/// signal frame at `sp`. Return the restored a0, which the syscall returns.
+pub fn sigreturn() -> SysResult<usize> {
+    let trap_cx = current_trap_cx();
+    let Ok(frame) = UserPtr::<SignalFrame>::new(trap_cx.x[2]).read() else {
+        force_signal(SigInfo::kernel(SIGSEGV));
+        return Err(SysError::EFAULT);
+    };
+    let regs = frame.ucontext.mcontext.regs;
+    trap_cx.sepc = regs[0];
+    trap_cx.x[1..].copy_from_slice(&regs[1..]);
+    current_thread()
+        .inner_exclusive_access()
+        .set_sig_mask(frame.ucontext.sigmask.difference(UNBLOCKABLE));
+    Ok(trap_cx.x[10])
+}
@@ -0,0 +1,10 @@
+use core::arch::global_asm;
+
+use super::context::ThreadContext;
+
+global_asm!(include_str!("switch.asm"));
+
+unsafe extern "C" {
+    /// Save callee-saved registers into `current_thread_cx_ptr` and resume `next_thread_cx_ptr`.
+    pub fn __switch(current_thread_cx_ptr: *mut ThreadContext, next_thread_cx_ptr: *const ThreadContext);
+}
@@ -1,63 +1,224 @@
-use alloc::sync::{Arc, Weak};
-use core::cell::RefMut;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 
 use spin::Mutex;
 
-use super::pcb::ProcessControlBlock;
-use crate::arch::process::{context::ThreadContext, thread_user_res::ThreadUserRes};
+use super::pcb::{Pid, ProcessControlBlock, pid_alloc};
+use crate::{
+    arch::{
+        mm::address::PhysPageNum,
+        process::{
+            context::ThreadContext,
+            kernel_stack::KernelStack,
+            scheduler::SchedEntity,
+            signal::{RestartBlock, SigPending, SigSet},
+            thread_user_res::ThreadUserRes,
+            wait_queue::WaitQueue,
+        },
+        trap::context::TrapContext,
+    },
+    error::SysResult,
+};
 
-// todo: kstack
 pub struct ThreadControlBlock {
     process: Weak<ProcessControlBlock>,
+    /// Thread id seen by the user, which is the pid for the main thread like Linux.
+    /// It's unrelated to the tid of `ThreadUserRes`, which only places the user resources.
+    tid: usize,
+    /// `None` for the main thread, whose tid is owned by the process
+    _tid_handle: Option<Pid>,
+    kstack: KernelStack,
     inner: Mutex<ThreadControlBlockInner>,
 }
 
-// todo: how to design trap context
 pub struct ThreadControlBlockInner {
     res: Option<ThreadUserRes>,
+    trap_cx_ppn: PhysPageNum,
     thread_context: ThreadContext,
     thread_status: ThreadStatus,
+    /// whether the thread is still on a hart, i.e. `__switch` may not have saved its context yet
+    on_cpu: bool,
+    sched_entity: SchedEntity,
     exit_code: i32,
+    /// set by `set_tid_address` or `CLONE_CHILD_CLEARTID`, 0 is written there on exit
+    clear_child_tid: usize,
+    /// the queue the thread is blocked in, `None` once it's woken up from there
+    wait_queue: Option<Arc<WaitQueue>>,
+    /// signals blocked by the thread
+    sig_mask: SigSet,
+    /// signals sent to this thread only, e.g. by tgkill or faults
+    sig_pending: SigPending,
+    /// keys of the PI futexes held by the thread which others have blocked on
+    pi_futexes: Vec<usize>,
+    /// saved by the last syscall interrupted to be restarted by `restart_syscall`
+    restart_block: Option<RestartBlock>,
 }
 
-// impl ThreadControlBlock {
-//     fn get_tid(&self) -> Tid {
-//         self.tid
-//     }
-// }
-
 impl ThreadControlBlock {
     pub fn inner_exclusive_access(&self) -> spin::MutexGuard<'_, ThreadControlBlockInner> {
         self.inner.lock()
     }
 
-    pub fn new(process: Arc<ProcessControlBlock>, user_stack_base: usize) -> Self {
-        let res = ThreadUserRes::new(process.clone(), user_stack_base);
-        Self {
+    /// The first thread created in a process becomes its main thread.
+    pub fn new(process: Arc<ProcessControlBlock>, user_stack_base: usize, alloc_user_res: bool) -> SysResult<Self> {
+        let (tid, tid_handle) = match process.inner_exclusive_access().thread_count() {
+            0 => (process.pid(), None),
+            _ => {
+                let tid_handle = pid_alloc();
+                (tid_handle.0, Some(tid_handle))
+            },
+        };
+        let res = ThreadUserRes::new(process.clone(), user_stack_base, alloc_user_res)?;
+        let trap_cx_ppn = res.trap_cx_ppn();
+        let kstack = KernelStack::new()?;
+        let kstack_top = kstack.top();
+        Ok(Self {
             process: Arc::downgrade(&process),
+            tid,
+            _tid_handle: tid_handle,
+            kstack,
             inner: Mutex::new(ThreadControlBlockInner {
                 res: Some(res),
-                thread_context: ThreadContext::zero_init(),
+                trap_cx_ppn,
+                thread_context: ThreadContext::goto_trap_return(kstack_top),
                 thread_status: ThreadStatus::Ready,
+                on_cpu: false,
+                sched_entity: SchedEntity::new(),
                 exit_code: 0,
+                clear_child_tid: 0,
+                wait_queue: None,
+                sig_mask: SigSet::empty(),
+                sig_pending: SigPending::new(),
+                pi_futexes: Vec::new(),
+                restart_block: None,
             }),
-        }
+        })
+    }
+
+    pub fn process(&self) -> Arc<ProcessControlBlock> {
+        self.process.upgrade().unwrap()
+    }
+
+    pub fn tid(&self) -> usize {
+        self.tid
+    }
+
+    pub fn kstack_top(&self) -> usize {
+        self.kstack.top()
     }
 }
 
 impl ThreadControlBlockInner {
-    fn get_status(&self) -> ThreadStatus {
+    pub fn get_status(&self) -> ThreadStatus {
         self.thread_status
     }
 
+    pub fn set_status(&mut self, status: ThreadStatus) {
+        self.thread_status = status;
+    }
+
+    pub fn is_on_cpu(&self) -> bool {
+        self.on_cpu
+    }
+
+    pub fn set_on_cpu(&mut self, on_cpu: bool) {
+        self.on_cpu = on_cpu;
+    }
+
+    pub fn sched_entity(&self) -> &SchedEntity {
+        &self.sched_entity
+    }
+
+    pub fn sched_entity_mut(&mut self) -> &mut SchedEntity {
+        &mut self.sched_entity
+    }
+
+    pub fn set_exit_code(&mut self, exit_code: i32) {
+        self.exit_code = exit_code;
+    }
+
+    pub fn clear_child_tid(&self) -> usize {
+        self.clear_child_tid
+    }
+
+    pub fn set_clear_child_tid(&mut self, addr: usize) {
+        self.clear_child_tid = addr;
+    }
+
+    pub fn wait_queue(&self) -> Option<Arc<WaitQueue>> {
+        self.wait_queue.clone()
+    }
+
+    pub fn set_wait_queue(&mut self, wait_queue: Option<Arc<WaitQueue>>) {
+        self.wait_queue = wait_queue;
+    }
+
+    pub fn sig_mask(&self) -> SigSet {
+        self.sig_mask
+    }
+
+    pub fn set_sig_mask(&mut self, sig_mask: SigSet) {
+        self.sig_mask = sig_mask;
+    }
+
+    pub fn sig_pending(&self) -> &SigPending {
+        &self.sig_pending
+    }
+
+    pub fn sig_pending_mut(&mut self) -> &mut SigPending {
+        &mut self.sig_pending
+    }
+
+    pub fn pi_futexes(&self) -> &[usize] {
+        &self.pi_futexes
+    }
+
+    pub fn pi_futexes_mut(&mut self) -> &mut Vec<usize> {
+        &mut self.pi_futexes
+    }
+
+    pub fn set_restart_block(&mut self, restart_block: RestartBlock) {
+        self.restart_block = Some(restart_block);
+    }
+
+    pub fn take_restart_block(&mut self) -> Option<RestartBlock> {
+        self.restart_block.take()
+    }
+
+    pub fn get_thread_context_ptr(&mut self) -> *mut ThreadContext {
+        &mut self.thread_context as *mut ThreadContext
+    }
+
     pub fn get_user_stack_base(&self) -> usize {
         self.res.as_ref().unwrap().user_stack_base
     }
+
+    pub fn res(&self) -> &ThreadUserRes {
+        self.res.as_ref().unwrap()
+    }
+
+    /// Take the user resources away, they are released when dropped.
+    pub fn take_res(&mut self) -> Option<ThreadUserRes> {
+        self.res.take()
+    }
+
+    /// The trap context page moves when exec replaces the memory set.
+    pub fn set_trap_cx_ppn(&mut self, trap_cx_ppn: PhysPageNum) {
+        self.trap_cx_ppn = trap_cx_ppn;
+        self.res.as_mut().unwrap().set_trap_cx_ppn(trap_cx_ppn);
+    }
+
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-enum ThreadStatus {
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ThreadStatus {
     Ready,
     Running,
     Blocked,
+    Exited,
 }
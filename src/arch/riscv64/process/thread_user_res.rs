@@ -1,43 +1,131 @@
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 
-use lazy_static::*;
-use spin::Mutex;
-
-use crate::arch::{mm::address::PhysPageNum, process::pcb::ProcessControlBlock, utils::QueueAllocator};
+use crate::{
+    arch::{
+        config::{MAIN_STACK_MAX, PAGE_SIZE, TRAP_CONTEXT_BASE, USER_STACK_SIZE},
+        mm::{
+            address::{PhysPageNum, VirtAddr},
+            map_area::{AreaType, MapPermission},
+            memory_set::MemorySet,
+        },
+        process::pcb::ProcessControlBlock,
+    },
+    error::SysResult,
+};
 
+/// User space resources of a thread: tid, user stack and trap context page.
 pub struct ThreadUserRes {
     tid: Tid,
     pub user_stack_base: usize,
     trap_cx_ppn: PhysPageNum,
+    process: Weak<ProcessControlBlock>,
 }
 
 impl ThreadUserRes {
-    // todo: 用户程序的地址空间是如何设计的
-    pub fn new(process: Arc<ProcessControlBlock>, user_stack_base: usize) -> Self {
+    pub fn new(process: Arc<ProcessControlBlock>, user_stack_base: usize, alloc_user_res: bool) -> SysResult<Self> {
         // alloc tid
         let tid = process.inner_exclusive_access().tid_allocator.lock().alloc();
-        ThreadUserRes {
+        let mut res = ThreadUserRes {
             tid: Tid(tid),
             user_stack_base,
-            trap_cx_ppn: PhysPageNum(1),
+            trap_cx_ppn: PhysPageNum(0),
+            process: Arc::downgrade(&process),
+        };
+        if alloc_user_res {
+            res.alloc_user_res()?;
         }
+        res.trap_cx_ppn = res.find_trap_cx_ppn();
+        Ok(res)
     }
-}
 
-#[derive(Clone, Debug)]
-struct Tid(usize);
+    /// Map the user stack and the trap context page of this thread, ENOMEM if the stack is over
+    /// `RLIMIT_AS`.
+    pub fn alloc_user_res(&self) -> SysResult<()> {
+        let process = self.process.upgrade().unwrap();
+        let mut process_inner = process.inner_exclusive_access();
+        process_inner.check_address_space(USER_STACK_SIZE)?;
+        self.map_user_res(&mut process_inner.memory)
+    }
 
-lazy_static! {
-    static ref TID_ALLOCATOR: Mutex<QueueAllocator> = Mutex::new(QueueAllocator::new());
-}
+    /// Map the user stack and the trap context page of this thread in `memory_set`, which may be
+    /// a new one for exec.
+    pub fn map_user_res(&self, memory_set: &mut MemorySet) -> SysResult<()> {
+        let ustack_top = self.ustack_top();
+        memory_set.insert_framed_area(
+            (ustack_top - USER_STACK_SIZE).into(),
+            ustack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+            AreaType::Stack,
+        )?;
+        let trap_cx_bottom = self.trap_cx_user_va();
+        memory_set.insert_framed_area(
+            trap_cx_bottom.into(),
+            (trap_cx_bottom + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+            AreaType::Trap,
+        )
+    }
+
+    fn dealloc_user_res(&self) {
+        let process = self.process.upgrade().unwrap();
+        let mut process_inner = process.inner_exclusive_access();
+        process_inner.memory.remove_thread_res(
+            VirtAddr::from(self.ustack_top()).floor(),
+            VirtAddr::from(self.trap_cx_user_va()).floor(),
+        );
+    }
+
+    fn find_trap_cx_ppn(&self) -> PhysPageNum {
+        let process = self.process.upgrade().unwrap();
+        let process_inner = process.inner_exclusive_access();
+        let trap_cx_bottom = VirtAddr::from(self.trap_cx_user_va());
+        process_inner
+            .memory
+            .page_table
+            .translate(trap_cx_bottom.floor())
+            .unwrap()
+            .ppn()
+    }
+
+    pub fn tid(&self) -> usize {
+        self.tid.0
+    }
 
-// RAII
-fn tid_alloc() -> Tid {
-    Tid(TID_ALLOCATOR.lock().alloc())
+    pub fn trap_cx_ppn(&self) -> PhysPageNum {
+        self.trap_cx_ppn
+    }
+
+    pub fn set_trap_cx_ppn(&mut self, trap_cx_ppn: PhysPageNum) {
+        self.trap_cx_ppn = trap_cx_ppn;
+    }
+
+    pub fn trap_cx_user_va(&self) -> usize {
+        TRAP_CONTEXT_BASE - self.tid.0 * PAGE_SIZE
+    }
+
+    /// Leave room for the stack of thread 0 to grow, and a guard page between adjacent user
+    /// stacks of the other threads.
+    pub fn ustack_top(&self) -> usize {
+        match self.tid.0 {
+            0 => self.user_stack_base,
+            tid => self.user_stack_base - MAIN_STACK_MAX - (tid - 1) * (USER_STACK_SIZE + PAGE_SIZE),
+        }
+    }
 }
 
-impl Drop for Tid {
+impl Drop for ThreadUserRes {
     fn drop(&mut self) {
-        TID_ALLOCATOR.lock().dealloc(self.0);
+        // the process is gone with its memory set, nothing to recycle
+        if let Some(process) = self.process.upgrade() {
+            self.dealloc_user_res();
+            process
+                .inner_exclusive_access()
+                .tid_allocator
+                .lock()
+                .dealloc(self.tid.0);
+        }
     }
 }
+
+#[derive(Clone, Debug)]
+struct Tid(usize);
@@ -0,0 +1,86 @@
+//! Input typed on the console, taken on timer ticks as a terminal driver would on interrupts.
+//!
+//! Control characters raise their signals as soon as they are typed, whether anyone is reading
+//! the console or not, and the rest is kept for `sys_read`.
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use super::{
+    pcb::console_foreground,
+    signal::{SIGINT, SIGQUIT, SIGTSTP, SigInfo, send_signal_to_group},
+    wait_queue::{WaitQueue, WaitResult},
+};
+use crate::{
+    arch::console,
+    error::{SysError, SysResult},
+};
+
+/// Characters typed and not read yet.
+static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+/// Held while taking characters from the console, so that they are kept in the order typed.
+static POLLING: Mutex<()> = Mutex::new(());
+
+lazy_static! {
+    /// Threads reading the console, woken up when there's input.
+    static ref READERS: Arc<WaitQueue> = Arc::new(WaitQueue::new());
+}
+
+/// The signal a control character typed on the console raises, like the `ISIG` mode of a
+/// terminal: ^C, ^\ and ^Z.
+fn keyboard_signal(c: u8) -> Option<usize> {
+    match c {
+        0x03 => Some(SIGINT),
+        0x1c => Some(SIGQUIT),
+        0x1a => Some(SIGTSTP),
+        _ => None,
+    }
+}
+
+/// Take what has been typed on the console. Called on timer ticks like `check_timers`, the hart
+/// already doing it is left to it.
+pub fn poll_console() {
+    let Some(_polling) = POLLING.try_lock() else {
+        return;
+    };
+    let mut typed = false;
+    while let Some(c) = console::get_char() {
+        match keyboard_signal(c) {
+            // sent to the foreground group instead of being read
+            Some(signo) => {
+                let _ = send_signal_to_group(console_foreground(), SigInfo::kernel(signo));
+            },
+            None => {
+                INPUT.lock().push_back(c);
+                typed = true;
+            },
+        }
+    }
+    if typed {
+        READERS.wake_all();
+    }
+}
+
+/// Take a character typed on the console, blocking until there's one.
+/// ERESTARTSYS if it's interrupted by a signal, e.g. the one typed by the user.
+pub fn read_console() -> SysResult<u8> {
+    poll_console();
+    loop {
+        let mut c = None;
+        let result = READERS.wait_if(
+            || {
+                c = INPUT.lock().pop_front();
+                c.is_none()
+            },
+            None,
+        );
+        match result {
+            WaitResult::NotBlocked => return Ok(c.unwrap()),
+            WaitResult::Interrupted => return Err(SysError::ERESTARTSYS),
+            // another reader may have taken it first
+            WaitResult::Woken | WaitResult::TimedOut => {},
+        }
+    }
+}
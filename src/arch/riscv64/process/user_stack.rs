@@ -0,0 +1,109 @@
+use alloc::{string::String, vec::Vec};
+use core::mem::size_of;
+
+use crate::{
+    arch::{
+        config::{PAGE_SIZE, USER_STACK_SIZE},
+        mm::memory_set::{ElfInfo, MemorySet},
+        timer::get_time,
+    },
+    error::{SysError, SysResult},
+};
+
+// auxiliary vector types, see `include/uapi/linux/auxvec.h`
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+const AT_RANDOM: usize = 25;
+
+/// Like Linux, arguments and environment strings may take up to a quarter of the stack.
+const ARG_MAX: usize = USER_STACK_SIZE / 4;
+
+/// Where the pieces of the initial stack end up, passed to the program in `sp`, `a0`, `a1`, `a2`.
+pub struct UserStack {
+    pub sp: usize,
+    pub argc: usize,
+    pub argv: usize,
+    pub envp: usize,
+}
+
+/// Build the initial stack of a program below `stack_top` the way Linux does, from the top:
+/// 16 random bytes, the strings, padding, then the auxiliary vector, `envp`, `argv` and `argc`,
+/// which `sp` points to.
+pub fn init_user_stack(
+    memory_set: &mut MemorySet,
+    stack_top: usize,
+    args: &[String],
+    envs: &[String],
+    elf_info: &ElfInfo,
+) -> SysResult<UserStack> {
+    let strings_len: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let pointers_len = (args.len() + envs.len() + 2) * size_of::<usize>();
+    if strings_len + pointers_len > ARG_MAX {
+        return Err(SysError::E2BIG);
+    }
+
+    let mut sp = stack_top;
+    sp -= 16;
+    let random = sp;
+    memory_set.write_bytes(random, &random_bytes())?;
+
+    let mut push_str = |s: &String| -> SysResult<usize> {
+        sp -= s.len() + 1;
+        memory_set.write_bytes(sp, s.as_bytes())?;
+        memory_set.write_bytes(sp + s.len(), &[0])?;
+        Ok(sp)
+    };
+    let mut envp: Vec<usize> = envs.iter().map(&mut push_str).collect::<SysResult<_>>()?;
+    let mut argv: Vec<usize> = args.iter().map(&mut push_str).collect::<SysResult<_>>()?;
+    envp.push(0);
+    argv.push(0);
+
+    let auxv = [
+        (AT_PHDR, elf_info.phdr),
+        (AT_PHENT, elf_info.phent),
+        (AT_PHNUM, elf_info.phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, elf_info.entry_point),
+        (AT_RANDOM, random),
+        (AT_NULL, 0),
+    ];
+    let mut words: Vec<usize> = Vec::new();
+    words.push(args.len());
+    words.extend_from_slice(&argv);
+    words.extend_from_slice(&envp);
+    auxv.iter()
+        .for_each(|&(key, value)| words.extend_from_slice(&[key, value]));
+
+    // sp must be 16-byte aligned when the program starts
+    sp = (sp - words.len() * size_of::<usize>()) & !0xf;
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+    memory_set.write_bytes(sp, &bytes)?;
+
+    Ok(UserStack {
+        sp,
+        argc: args.len(),
+        argv: sp + size_of::<usize>(),
+        envp: sp + (argv.len() + 1) * size_of::<usize>(),
+    })
+}
+
+/// Seed of the stack protector and the like, there's no better entropy source than the timer yet.
+fn random_bytes() -> [u8; 16] {
+    // splitmix64
+    let mut state = get_time() as u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&next().to_ne_bytes());
+    bytes[8..].copy_from_slice(&next().to_ne_bytes());
+    bytes
+}
@@ -0,0 +1,217 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use spin::Mutex;
+
+use super::{
+    current_thread,
+    scheduler::{schedule, wakeup_thread},
+    signal::has_pending_signal,
+    tcb::{ThreadControlBlock, ThreadStatus},
+};
+use crate::arch::timer::get_time;
+
+/// Threads waiting with a deadline in timer cycles, by the deadline and the tid.
+///
+/// Threads are woken up with the lock held, so that a wait which has cancelled its timer is
+/// never woken up by it afterwards.
+static TIMERS: Mutex<BTreeMap<(usize, usize), Arc<ThreadControlBlock>>> = Mutex::new(BTreeMap::new());
+
+/// Threads blocked until something happens, e.g. a futex word changes or a child exits.
+///
+/// A waiting thread remembers which queue it's in, so that it can leave by itself when it's woken
+/// up otherwise, e.g. by its deadline or `exit_group`.
+/// The lock of a queue is taken before the inner lock of any thread.
+pub struct WaitQueue {
+    threads: Mutex<VecDeque<Arc<ThreadControlBlock>>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WaitResult {
+    /// the condition didn't hold, so the thread didn't block at all
+    NotBlocked,
+    /// woken up from the queue
+    Woken,
+    /// the deadline passed
+    TimedOut,
+    /// woken up by someone else, e.g. a signal or `exit_group`
+    Interrupted,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            threads: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Block the current thread on this queue if `should_block` holds, until it's woken up from
+    /// the queue, or until `deadline` in timer cycles if there's one.
+    /// The wait is interrupted by signals, see `has_pending_signal`.
+    pub fn wait_if(self: &Arc<Self>, should_block: impl FnOnce() -> bool, deadline: Option<usize>) -> WaitResult {
+        self.wait(should_block, deadline, has_pending_signal)
+    }
+
+    /// Like `wait_if`, but the wait is only interrupted if the process is exiting.
+    pub fn wait_killable_if(
+        self: &Arc<Self>,
+        should_block: impl FnOnce() -> bool,
+        deadline: Option<usize>,
+    ) -> WaitResult {
+        self.wait(should_block, deadline, || current_thread().process().is_exiting())
+    }
+
+    /// `should_block` is checked with the queue locked, so no wakeup from the queue is lost as
+    /// long as wakers change what it checks before waking up the queue. The thread is marked as
+    /// blocked before that, so `wakeup_thread` after changing what it or `interrupted` checks
+    /// isn't lost either.
+    fn wait(
+        self: &Arc<Self>,
+        should_block: impl FnOnce() -> bool,
+        deadline: Option<usize>,
+        interrupted: impl FnOnce() -> bool,
+    ) -> WaitResult {
+        let thread = current_thread();
+        let mut threads = self.threads.lock();
+        thread.inner_exclusive_access().set_status(ThreadStatus::Blocked);
+        let result = if interrupted() {
+            Some(WaitResult::Interrupted)
+        } else if !should_block() {
+            Some(WaitResult::NotBlocked)
+        } else {
+            None
+        };
+        if let Some(result) = result {
+            thread.inner_exclusive_access().set_status(ThreadStatus::Running);
+            return result;
+        }
+        thread.inner_exclusive_access().set_wait_queue(Some(self.clone()));
+        threads.push_back(thread.clone());
+        drop(threads);
+        if let Some(deadline) = deadline {
+            TIMERS.lock().insert((deadline, thread.tid()), thread.clone());
+        }
+        drop(thread);
+        schedule();
+
+        let thread = current_thread();
+        if let Some(deadline) = deadline {
+            TIMERS.lock().remove(&(deadline, thread.tid()));
+        }
+        if !leave_wait_queue(&thread) {
+            WaitResult::Woken
+        } else if deadline.is_some_and(|deadline| get_time() >= deadline) {
+            WaitResult::TimedOut
+        } else {
+            WaitResult::Interrupted
+        }
+    }
+
+    /// Wake up the most favorable waiter, the earliest one among equals like Linux.
+    pub fn wake_one(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.wake_one_with(|_, _| {})
+    }
+
+    /// Like `wake_one`, but call `before_wake` with the queue still locked, with the waiter if
+    /// there's one and whether others are left, e.g. to hand a lock over to it.
+    pub fn wake_one_with(
+        &self,
+        before_wake: impl FnOnce(Option<&Arc<ThreadControlBlock>>, bool),
+    ) -> Option<Arc<ThreadControlBlock>> {
+        let mut threads = self.threads.lock();
+        let Some(idx) = most_favorable(&threads) else {
+            before_wake(None, false);
+            return None;
+        };
+        let thread = threads.remove(idx).unwrap();
+        thread.inner_exclusive_access().set_wait_queue(None);
+        before_wake(Some(&thread), !threads.is_empty());
+        wakeup_thread(thread.clone());
+        Some(thread)
+    }
+
+    /// Wake up at most `count` waiters, return how many are woken up.
+    pub fn wake(&self, count: usize) -> usize {
+        (0..count).take_while(|_| self.wake_one().is_some()).count()
+    }
+
+    pub fn wake_all(&self) -> usize {
+        self.wake(usize::MAX)
+    }
+
+    /// Move at most `count` waiters to `other`, return how many are moved.
+    /// Callers must make sure no one moves waiters the other way at the same time.
+    pub fn requeue(&self, other: &Arc<Self>, count: usize) -> usize {
+        if Arc::as_ptr(other) == self as *const Self {
+            return 0;
+        }
+        let mut threads = self.threads.lock();
+        let mut other_threads = other.threads.lock();
+        let mut moved = 0;
+        while moved < count {
+            let Some(idx) = most_favorable(&threads) else {
+                break;
+            };
+            let thread = threads.remove(idx).unwrap();
+            thread.inner_exclusive_access().set_wait_queue(Some(other.clone()));
+            other_threads.push_back(thread);
+            moved += 1;
+        }
+        moved
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.threads.lock().is_empty()
+    }
+
+    /// Priority of the most favorable waiter.
+    pub fn highest_prio(&self) -> Option<i32> {
+        let threads = self.threads.lock();
+        most_favorable(&threads).map(|idx| threads[idx].inner_exclusive_access().sched_entity().prio())
+    }
+}
+
+fn most_favorable(threads: &VecDeque<Arc<ThreadControlBlock>>) -> Option<usize> {
+    threads
+        .iter()
+        .enumerate()
+        .min_by_key(|(idx, thread)| (thread.inner_exclusive_access().sched_entity().prio(), *idx))
+        .map(|(idx, _)| idx)
+}
+
+/// Remove `thread` from the queue it's waiting in, return whether it was still there.
+/// Otherwise it has been woken up from a queue.
+fn leave_wait_queue(thread: &Arc<ThreadControlBlock>) -> bool {
+    loop {
+        let Some(queue) = thread.inner_exclusive_access().wait_queue() else {
+            return false;
+        };
+        let mut threads = queue.threads.lock();
+        if let Some(idx) = threads.iter().position(|waiter| Arc::ptr_eq(waiter, thread)) {
+            threads.remove(idx);
+            thread.inner_exclusive_access().set_wait_queue(None);
+            return true;
+        }
+        // woken up or moved to another queue in the meantime, which is done with the queue locked
+    }
+}
+
+/// Block the current thread until `deadline` in timer cycles, or until it's woken up otherwise.
+pub fn sleep_until(deadline: usize) -> WaitResult {
+    Arc::new(WaitQueue::new()).wait_if(|| get_time() < deadline, Some(deadline))
+}
+
+/// Wake up the threads whose deadline has passed.
+/// Called on timer ticks from user mode and by idle harts, never with a run queue locked.
+pub fn check_timers() {
+    let now = get_time();
+    let mut timers = TIMERS.lock();
+    while let Some(entry) = timers.first_entry() {
+        if entry.key().0 > now {
+            break;
+        }
+        wakeup_thread(entry.remove());
+    }
+}
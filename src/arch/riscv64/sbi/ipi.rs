@@ -0,0 +1,11 @@
+use super::sbi_call;
+
+const EID_IPI: usize = 0x735049;
+
+const FID_SEND_IPI: usize = 0;
+
+/// Raise a supervisor software interrupt on `hart_id`, e.g. to have it reschedule.
+pub fn send_ipi(hart_id: usize) {
+    // a mask of the single hart based at its id
+    sbi_call(EID_IPI, FID_SEND_IPI, 1, hart_id, 0);
+}
@@ -1,11 +1,15 @@
 use core::arch::asm;
 
 mod hsm;
+mod ipi;
 mod legacy;
+mod rfence;
 mod timer;
 
 pub use hsm::*;
+pub use ipi::send_ipi;
 pub use legacy::*;
+pub use rfence::{remote_sfence_vma, remote_sfence_vma_all};
 pub use timer::set_timer;
 
 
@@ -25,3 +29,21 @@ fn sbi_call(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> us
     }
     ret
 }
+
+/// sbi call with 4 arguments, e.g. for remote fences
+#[inline(always)]
+fn sbi_call4(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> usize {
+    let mut ret;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => ret,
+            in("x11") arg1,
+            in("x12") arg2,
+            in("x13") arg3,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    ret
+}
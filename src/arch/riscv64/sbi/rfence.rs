@@ -0,0 +1,17 @@
+use super::sbi_call4;
+
+const EID_RFENCE: usize = 0x52464E43;
+
+const FID_REMOTE_SFENCE_VMA: usize = 1;
+
+/// Flush the entries of `[start, start + size)` from the TLB of every other hart, e.g. after a
+/// user page is moved to another frame.
+pub fn remote_sfence_vma(start: usize, size: usize) {
+    sbi_call4(EID_RFENCE, FID_REMOTE_SFENCE_VMA, 0, usize::MAX, start, size);
+}
+
+/// Flush the whole TLB of every other hart, e.g. after user pages are made less accessible.
+pub fn remote_sfence_vma_all() {
+    // a mask base of -1 means all harts, and a size of -1 means the whole address space
+    sbi_call4(EID_RFENCE, FID_REMOTE_SFENCE_VMA, 0, usize::MAX, 0, usize::MAX);
+}
@@ -0,0 +1,68 @@
+use crate::{
+    arch::{
+        config::PAGE_SIZE,
+        mm::user_ptr::{UserPtr, UserSlice},
+        process::{current_process, read_console, signal::SIGTTIN},
+    },
+    error::{SysError, SysResult},
+    logging::print_bytes,
+};
+
+const FD_STDIN: usize = 0;
+const FD_STDOUT: usize = 1;
+const FD_STDERR: usize = 2;
+
+/// `tcgetpgrp`
+const TIOCGPGRP: usize = 0x540f;
+/// `tcsetpgrp`
+const TIOCSPGRP: usize = 0x5410;
+
+pub fn sys_write(fd: usize, buf: UserSlice) -> SysResult<usize> {
+    match fd {
+        FD_STDOUT | FD_STDERR => {
+            let mut kernel_buf = [0u8; PAGE_SIZE];
+            for offset in (0..buf.len()).step_by(PAGE_SIZE) {
+                let len = PAGE_SIZE.min(buf.len() - offset);
+                buf.slice(offset, len).read(&mut kernel_buf[..len])?;
+                print_bytes(&kernel_buf[..len]);
+            }
+            Ok(buf.len())
+        },
+        _ => Err(SysError::EBADF),
+    }
+}
+
+pub fn sys_read(fd: usize, buf: UserSlice) -> SysResult<usize> {
+    match fd {
+        FD_STDIN => {
+            current_process().check_foreground(SIGTTIN)?;
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            let c = read_console()?;
+            buf.write(&[c])?;
+            Ok(1)
+        },
+        _ => Err(SysError::EBADF),
+    }
+}
+
+/// Only the job control requests on the console are supported, which all of the standard fds are.
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> SysResult<usize> {
+    if fd > FD_STDERR {
+        return Err(SysError::EBADF);
+    }
+    let process = current_process();
+    match request {
+        TIOCGPGRP => UserPtr::<i32>::new(arg).write(process.tcgetpgrp()? as i32)?,
+        TIOCSPGRP => {
+            let pgid = UserPtr::<i32>::new(arg).read()?;
+            if pgid < 0 {
+                return Err(SysError::EINVAL);
+            }
+            process.tcsetpgrp(pgid as usize)?;
+        },
+        _ => return Err(SysError::ENOTTY),
+    }
+    Ok(0)
+}
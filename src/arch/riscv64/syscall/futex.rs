@@ -0,0 +1,52 @@
+use crate::{
+    arch::{
+        mm::user_ptr::UserPtr,
+        process::{futex_lock_pi, futex_requeue, futex_unlock_pi, futex_wait, futex_wake},
+        timer::{TimeSpec, get_time},
+    },
+    error::{SysError, SysResult},
+};
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_REQUEUE: usize = 3;
+const FUTEX_CMP_REQUEUE: usize = 4;
+const FUTEX_LOCK_PI: usize = 6;
+const FUTEX_UNLOCK_PI: usize = 7;
+const FUTEX_TRYLOCK_PI: usize = 8;
+/// Futexes are keyed by physical address whether they are private or not.
+const FUTEX_PRIVATE_FLAG: usize = 128;
+const FUTEX_CLOCK_REALTIME: usize = 256;
+
+/// `timeout` is relative for `FUTEX_WAIT` and absolute for `FUTEX_LOCK_PI`, and it's the count
+/// of waiters to move for `FUTEX_REQUEUE` and `FUTEX_CMP_REQUEUE`.
+/// There's no wall clock yet, so absolute time is counted from boot.
+pub fn sys_futex(
+    uaddr: usize,
+    futex_op: usize,
+    val: u32,
+    timeout: usize,
+    uaddr2: usize,
+    val3: u32,
+) -> SysResult<usize> {
+    let read_timeout = || -> SysResult<Option<usize>> {
+        let timeout = UserPtr::<TimeSpec>::new(timeout);
+        match timeout.is_null() {
+            true => Ok(None),
+            false => Ok(Some(timeout.read()?.to_cycles()?)),
+        }
+    };
+    match futex_op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT => {
+            let deadline = read_timeout()?.map(|timeout| get_time().saturating_add(timeout));
+            futex_wait(uaddr, val, deadline)
+        },
+        FUTEX_WAKE => futex_wake(uaddr, val as usize),
+        FUTEX_REQUEUE => futex_requeue(uaddr, val as usize, timeout, uaddr2, None),
+        FUTEX_CMP_REQUEUE => futex_requeue(uaddr, val as usize, timeout, uaddr2, Some(val3)),
+        FUTEX_LOCK_PI => futex_lock_pi(uaddr, read_timeout()?, false),
+        FUTEX_UNLOCK_PI => futex_unlock_pi(uaddr),
+        FUTEX_TRYLOCK_PI => futex_lock_pi(uaddr, None, true),
+        _ => Err(SysError::ENOSYS),
+    }
+}
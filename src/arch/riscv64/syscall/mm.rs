@@ -0,0 +1,156 @@
+use bitflags::bitflags;
+
+use crate::{
+    arch::{
+        config::{MMAP_BASE, MMAP_MIN_ADDR, PAGE_SIZE, USER_SPACE_END},
+        mm::map_area::MapPermission,
+        process::current_process,
+    },
+    error::{SysError, SysResult},
+};
+
+/// Move the program break to `addr` and return the new one. Like Linux, the current one is
+/// returned if it can't be moved, e.g. for `brk(0)`.
+pub fn sys_brk(addr: usize) -> SysResult<usize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let brk = inner.memory.brk();
+    // the pages the heap grows by, if it does
+    let grown = addr
+        .checked_next_multiple_of(PAGE_SIZE)
+        .map(|end| end.saturating_sub(brk.next_multiple_of(PAGE_SIZE)));
+    if grown.is_some_and(|grown| inner.check_address_space(grown).is_ok()) {
+        let _ = inner.memory.set_brk(addr);
+    }
+    Ok(inner.memory.brk())
+}
+
+/// `MAP_SHARED | MAP_PRIVATE`, which is `MAP_SHARED` rejecting flags it doesn't know.
+const MAP_SHARED_VALIDATE: usize = 0x03;
+
+bitflags! {
+    /// `PROT_*` of mmap and mprotect.
+    #[derive(Clone, Copy)]
+    pub struct MmapProt: usize {
+        const READ = 0x1;
+        const WRITE = 0x2;
+        const EXEC = 0x4;
+    }
+
+    /// Flags of mmap, see `include/uapi/linux/mman.h`.
+    #[derive(Clone, Copy)]
+    pub struct MmapFlags: usize {
+        const SHARED = 0x01;
+        const PRIVATE = 0x02;
+        const FIXED = 0x10;
+        const ANONYMOUS = 0x20;
+        const NORESERVE = 0x4000;
+        const POPULATE = 0x8000;
+        const STACK = 0x2_0000;
+        const FIXED_NOREPLACE = 0x10_0000;
+    }
+}
+
+impl MmapProt {
+    /// A writable page is readable as well, as riscv has no write-only pages.
+    fn map_perm(self) -> MapPermission {
+        let mut perm = MapPermission::U;
+        if self.intersects(Self::READ | Self::WRITE) {
+            perm |= MapPermission::R;
+        }
+        if self.contains(Self::WRITE) {
+            perm |= MapPermission::W;
+        }
+        if self.contains(Self::EXEC) {
+            perm |= MapPermission::X;
+        }
+        perm
+    }
+}
+
+/// Map `len` bytes of anonymous memory and return where. It's at `addr` with `MAP_FIXED`, which
+/// replaces what's there, or `MAP_FIXED_NOREPLACE`, and `addr` is a hint otherwise.
+/// Pages are populated on first touch whether it's `MAP_POPULATE` or not.
+///
+/// Files can't be mapped yet, see TODO.md: a mapping without `MAP_ANONYMOUS` fails with EBADF.
+pub fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, _fd: usize, offset: usize) -> SysResult<usize> {
+    let perm = MmapProt::from_bits(prot).ok_or(SysError::EINVAL)?.map_perm();
+    // unknown flags are ignored like Linux, unless it's asked to validate them
+    let flags = match flags & MAP_SHARED_VALIDATE == MAP_SHARED_VALIDATE {
+        true => MmapFlags::from_bits(flags).ok_or(SysError::EOPNOTSUPP)?,
+        false => MmapFlags::from_bits_truncate(flags),
+    };
+    if len == 0 || offset % PAGE_SIZE != 0 {
+        return Err(SysError::EINVAL);
+    }
+    // MAP_SHARED_VALIDATE has both
+    let shared = match (flags.contains(MmapFlags::SHARED), flags.contains(MmapFlags::PRIVATE)) {
+        (true, _) => true,
+        (false, true) => false,
+        (false, false) => return Err(SysError::EINVAL),
+    };
+    if !flags.contains(MmapFlags::ANONYMOUS) {
+        return Err(SysError::EBADF);
+    }
+    let len = len.checked_next_multiple_of(PAGE_SIZE).ok_or(SysError::ENOMEM)?;
+    if len > MMAP_BASE - MMAP_MIN_ADDR {
+        return Err(SysError::ENOMEM);
+    }
+
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    // what's there is replaced by MAP_FIXED, which it doesn't take more address space for
+    let (start, replaced) = match flags.intersects(MmapFlags::FIXED | MmapFlags::FIXED_NOREPLACE) {
+        true => {
+            if addr % PAGE_SIZE != 0 {
+                return Err(SysError::EINVAL);
+            }
+            if addr < MMAP_MIN_ADDR {
+                return Err(SysError::EPERM);
+            }
+            if addr > MMAP_BASE.saturating_sub(len) {
+                return Err(SysError::ENOMEM);
+            }
+            if !inner.memory.is_free(addr, len) && flags.contains(MmapFlags::FIXED_NOREPLACE) {
+                return Err(SysError::EEXIST);
+            }
+            (addr, inner.memory.user_size_in(addr, len))
+        },
+        false => (inner.memory.find_free_range(addr, len).ok_or(SysError::ENOMEM)?, 0),
+    };
+    // nothing is unmapped until the new area is known to fit
+    inner.check_address_space(len - replaced)?;
+    if replaced != 0 {
+        inner.memory.munmap(start, len)?;
+    }
+    inner.memory.mmap(start, len, perm, shared)?;
+    Ok(start)
+}
+
+/// Unmap the pages in `len` bytes at `addr`, it's not an error if some are not mapped.
+pub fn sys_munmap(addr: usize, len: usize) -> SysResult<usize> {
+    let len = len.checked_next_multiple_of(PAGE_SIZE).ok_or(SysError::EINVAL)?;
+    if addr % PAGE_SIZE != 0 || len == 0 || addr > USER_SPACE_END.saturating_sub(len) {
+        return Err(SysError::EINVAL);
+    }
+    current_process().inner_exclusive_access().memory.munmap(addr, len)?;
+    Ok(0)
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> SysResult<usize> {
+    let perm = MmapProt::from_bits(prot).ok_or(SysError::EINVAL)?.map_perm();
+    let len = len.checked_next_multiple_of(PAGE_SIZE).ok_or(SysError::ENOMEM)?;
+    if addr % PAGE_SIZE != 0 {
+        return Err(SysError::EINVAL);
+    }
+    if addr > USER_SPACE_END.saturating_sub(len) {
+        return Err(SysError::ENOMEM);
+    }
+    if len != 0 {
+        current_process()
+            .inner_exclusive_access()
+            .memory
+            .mprotect(addr, len, perm)?;
+    }
+    Ok(0)
+}
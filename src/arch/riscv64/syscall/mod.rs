@@ -1 +1,125 @@
+mod fs;
+mod futex;
+mod mm;
+mod process;
+mod resource;
+mod sched;
+mod signal;
+mod time;
+
+use fs::*;
+use futex::*;
+use log::warn;
+use mm::*;
+use process::*;
+use resource::*;
+use sched::*;
+use signal::*;
+use time::*;
+
+use crate::{
+    arch::mm::user_ptr::{UserPtr, UserSlice},
+    error::{SysError, SysResult},
+};
+
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_READ: usize = 63;
+const SYSCALL_WRITE: usize = 64;
+const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_SET_TID_ADDRESS: usize = 96;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
+const SYSCALL_SCHED_GETSCHEDULER: usize = 120;
+const SYSCALL_SCHED_GETPARAM: usize = 121;
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
+const SYSCALL_SCHED_YIELD: usize = 124;
+const SYSCALL_SCHED_GET_PRIORITY_MAX: usize = 125;
+const SYSCALL_SCHED_GET_PRIORITY_MIN: usize = 126;
+pub const SYSCALL_RESTART_SYSCALL: usize = 128;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_TKILL: usize = 130;
+const SYSCALL_TGKILL: usize = 131;
+const SYSCALL_RT_SIGACTION: usize = 134;
+const SYSCALL_RT_SIGPROCMASK: usize = 135;
+const SYSCALL_RT_SIGPENDING: usize = 136;
+pub const SYSCALL_RT_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETPPID: usize = 173;
 const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_CLONE: usize = 220;
+const SYSCALL_EXECVE: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_WAIT4: usize = 260;
+const SYSCALL_PRLIMIT64: usize = 261;
+
+/// Handle a syscall from user, `args` are taken from a0~a5.
+/// The return value will be written back to a0, errors as negative errno.
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    let result: SysResult<usize> = match syscall_id {
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_READ => sys_read(args[0], UserSlice::new(args[1], args[2])),
+        SYSCALL_WRITE => sys_write(args[0], UserSlice::new(args[1], args[2])),
+        SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
+        SYSCALL_SET_TID_ADDRESS => sys_set_tid_address(args[0]),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2] as u32, args[3], args[4], args[5] as u32),
+        SYSCALL_NANOSLEEP => sys_nanosleep(UserPtr::new(args[0]), UserPtr::new(args[1])),
+        SYSCALL_SCHED_SETSCHEDULER => sys_sched_setscheduler(args[0], args[1], UserPtr::new(args[2])),
+        SYSCALL_SCHED_GETSCHEDULER => sys_sched_getscheduler(args[0]),
+        SYSCALL_SCHED_GETPARAM => sys_sched_getparam(args[0], UserPtr::new(args[1])),
+        SYSCALL_SCHED_SETAFFINITY => sys_sched_setaffinity(args[0], args[1], UserPtr::new(args[2])),
+        SYSCALL_SCHED_GETAFFINITY => sys_sched_getaffinity(args[0], args[1], UserPtr::new(args[2])),
+        SYSCALL_SCHED_YIELD => sys_sched_yield(),
+        SYSCALL_SCHED_GET_PRIORITY_MAX => sys_sched_get_priority_max(args[0]),
+        SYSCALL_SCHED_GET_PRIORITY_MIN => sys_sched_get_priority_min(args[0]),
+        SYSCALL_RESTART_SYSCALL => sys_restart_syscall(),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1]),
+        SYSCALL_TKILL => sys_tkill(args[0], args[1]),
+        SYSCALL_TGKILL => sys_tgkill(args[0], args[1], args[2]),
+        SYSCALL_RT_SIGACTION => sys_rt_sigaction(args[0], UserPtr::new(args[1]), UserPtr::new(args[2]), args[3]),
+        SYSCALL_RT_SIGPROCMASK => sys_rt_sigprocmask(args[0], UserPtr::new(args[1]), UserPtr::new(args[2]), args[3]),
+        SYSCALL_RT_SIGPENDING => sys_rt_sigpending(UserPtr::new(args[0]), args[1]),
+        SYSCALL_RT_SIGRETURN => sys_rt_sigreturn(),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1], args[2] as i32),
+        SYSCALL_GETPRIORITY => sys_getpriority(args[0], args[1]),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1] as isize),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], UserPtr::new(args[1])),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], UserPtr::new(args[1])),
+        SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETPPID => sys_getppid(),
+        SYSCALL_GETTID => sys_gettid(),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_CLONE => sys_clone(args[0], args[1], UserPtr::new(args[2]), args[3], UserPtr::new(args[4])),
+        SYSCALL_EXECVE => sys_execve(UserPtr::new(args[0]), UserPtr::new(args[1]), UserPtr::new(args[2])),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_WAIT4 => sys_wait4(args[0] as isize, UserPtr::new(args[1]), args[2], args[3]),
+        SYSCALL_PRLIMIT64 => sys_prlimit64(args[0], args[1], UserPtr::new(args[2]), UserPtr::new(args[3])),
+        _ => {
+            warn!("Unsupported syscall_id: {}", syscall_id);
+            Err(SysError::ENOSYS)
+        },
+    };
+    match result {
+        Ok(ret) => ret as isize,
+        Err(err) => err.code(),
+    }
+}
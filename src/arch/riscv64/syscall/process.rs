@@ -0,0 +1,202 @@
+use alloc::{string::String, vec::Vec};
+
+use bitflags::bitflags;
+use log::info;
+
+use crate::{
+    arch::{
+        config::PAGE_SIZE,
+        mm::user_ptr::UserPtr,
+        process::{
+            PROCESS_MANAGER, WaitOptions, WaitResult, add_thread, current_process, current_thread, exit_current,
+        },
+    },
+    error::{SysError, SysResult},
+    loader::get_app_data_by_name,
+};
+
+/// Exit the calling thread, the process exits with the last one.
+pub fn sys_exit(exit_code: i32) -> ! {
+    exit_current(exit_code)
+}
+
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    info!(
+        "[kernel] process {} exited with code {}",
+        current_process().pid(),
+        exit_code
+    );
+    current_process().exit_group(exit_code);
+    exit_current(exit_code)
+}
+
+pub fn sys_getpid() -> SysResult<usize> {
+    Ok(current_process().pid())
+}
+
+pub fn sys_getppid() -> SysResult<usize> {
+    Ok(current_process().ppid())
+}
+
+pub fn sys_gettid() -> SysResult<usize> {
+    Ok(current_thread().tid())
+}
+
+pub fn sys_set_tid_address(tidptr: usize) -> SysResult<usize> {
+    let thread = current_thread();
+    thread.inner_exclusive_access().set_clear_child_tid(tidptr);
+    Ok(thread.tid())
+}
+
+/// Low byte of the clone flags, the signal sent to the parent when the child exits.
+const CSIGNAL: usize = 0xff;
+
+bitflags! {
+    /// Flags of clone except `CSIGNAL`, see `include/uapi/linux/sched.h`.
+    #[derive(Clone, Copy)]
+    pub struct CloneFlags: usize {
+        const VM = 0x0000_0100;
+        const FS = 0x0000_0200;
+        const FILES = 0x0000_0400;
+        const SIGHAND = 0x0000_0800;
+        const VFORK = 0x0000_4000;
+        const THREAD = 0x0001_0000;
+        const SYSVSEM = 0x0004_0000;
+        const SETTLS = 0x0008_0000;
+        const PARENT_SETTID = 0x0010_0000;
+        const CHILD_CLEARTID = 0x0020_0000;
+        const DETACHED = 0x0040_0000;
+        const CHILD_SETTID = 0x0100_0000;
+    }
+}
+
+/// Create a thread in the calling process with `CLONE_THREAD`, or fork it otherwise.
+/// There's nothing to share but the memory yet, so flags like `CLONE_FILES` are taken as given.
+///
+/// With `CLONE_VFORK` the caller is blocked until the child calls execve or exits, see
+/// `ProcessControlBlock::wait_vfork`, and `CLONE_VM` is allowed along with it like
+/// `posix_spawn` does, though the memory is copied. Processes can't share memory otherwise, so
+/// `CLONE_VM` alone is ENOSYS.
+pub fn sys_clone(flags: usize, stack: usize, ptid: UserPtr<i32>, tls: usize, ctid: UserPtr<i32>) -> SysResult<usize> {
+    let flags = CloneFlags::from_bits(flags & !CSIGNAL).ok_or(SysError::EINVAL)?;
+    let process = current_process();
+    let mut vfork_child = None;
+    let thread = if flags.contains(CloneFlags::THREAD) {
+        if !flags.contains(CloneFlags::VM | CloneFlags::SIGHAND) {
+            return Err(SysError::EINVAL);
+        }
+        process.clone_thread(stack, flags.contains(CloneFlags::SETTLS).then_some(tls))?
+    } else if flags.contains(CloneFlags::VM) && !flags.contains(CloneFlags::VFORK) {
+        return Err(SysError::ENOSYS);
+    } else {
+        let (child, child_thread) = process.fork(stack)?;
+        if flags.contains(CloneFlags::VFORK) {
+            // before it can run, so it can't exit first
+            child.start_vfork();
+            vfork_child = Some(child.clone());
+        }
+        if flags.contains(CloneFlags::CHILD_SETTID) {
+            let tid = (child_thread.tid() as i32).to_ne_bytes();
+            let _ = child.inner_exclusive_access().memory.write_bytes(ctid.addr(), &tid);
+        }
+        child_thread
+    };
+    let tid = thread.tid();
+    // like Linux, errors writing the tids are ignored, in the fork branch as well
+    if flags.contains(CloneFlags::PARENT_SETTID) {
+        let _ = ptid.write(tid as i32);
+    }
+    if flags.contains(CloneFlags::THREAD | CloneFlags::CHILD_SETTID) {
+        let _ = ctid.write(tid as i32);
+    }
+    if flags.contains(CloneFlags::CHILD_CLEARTID) {
+        thread.inner_exclusive_access().set_clear_child_tid(ctid.addr());
+    }
+    add_thread(thread);
+    if let Some(child) = vfork_child {
+        child.wait_vfork();
+    }
+    Ok(tid)
+}
+
+const PATH_MAX: usize = 4096;
+/// Longest single argument or environment string.
+const MAX_ARG_STRLEN: usize = PAGE_SIZE * 32;
+
+/// Return `argc`, which the syscall return value overwrites `a0` with.
+pub fn sys_execve(path: UserPtr<u8>, argv: UserPtr<usize>, envp: UserPtr<usize>) -> SysResult<usize> {
+    let path = path.read_cstr(PATH_MAX)?;
+    let args = read_cstr_array(argv)?;
+    let envs = read_cstr_array(envp)?;
+    info!("[kernel] execve {path} {args:?}");
+    let elf_data = get_app_data_by_name(&path).ok_or(SysError::ENOENT)?;
+    current_process().exec(elf_data, &args, &envs)?;
+    Ok(args.len())
+}
+
+/// Read a NULL-terminated array of strings like `argv`, a NULL array is taken as an empty one.
+fn read_cstr_array(array: UserPtr<usize>) -> SysResult<Vec<String>> {
+    let mut strings = Vec::new();
+    if array.is_null() {
+        return Ok(strings);
+    }
+    for i in 0.. {
+        let ptr = UserPtr::<u8>::new(array.add(i).read()?);
+        if ptr.is_null() {
+            break;
+        }
+        strings.push(ptr.read_cstr(MAX_ARG_STRLEN)?);
+    }
+    Ok(strings)
+}
+
+/// Wait for a child to exit and reap it, or to stop or continue with `WUNTRACED` or `WCONTINUED`.
+/// Return its pid or 0 if there's none with `WNOHANG`.
+pub fn sys_wait4(pid: isize, wstatus: UserPtr<i32>, options: usize, _rusage: usize) -> SysResult<usize> {
+    let options = WaitOptions::from_bits_truncate(options);
+    let mut interrupted = false;
+    loop {
+        if let Some((pid, status)) = current_process().wait_child(pid, options)? {
+            if !wstatus.is_null() {
+                wstatus.write(status)?;
+            }
+            return Ok(pid);
+        }
+        if options.contains(WaitOptions::NOHANG) {
+            return Ok(0);
+        }
+        // a child which has exited is reaped even if SIGCHLD interrupts the wait, like Linux
+        if interrupted {
+            return Err(SysError::ERESTARTSYS);
+        }
+        interrupted = current_process().wait_child_change(pid, options) == WaitResult::Interrupted;
+    }
+}
+
+pub fn sys_setpgid(pid: usize, pgid: isize) -> SysResult<usize> {
+    if pgid < 0 {
+        return Err(SysError::EINVAL);
+    }
+    current_process().setpgid(pid, pgid as usize)?;
+    Ok(0)
+}
+
+/// Group of process `pid`, the caller if it's 0.
+pub fn sys_getpgid(pid: usize) -> SysResult<usize> {
+    match pid {
+        0 => Ok(current_process().pgid()),
+        pid => Ok(PROCESS_MANAGER.get_process(pid).ok_or(SysError::ESRCH)?.pgid()),
+    }
+}
+
+pub fn sys_setsid() -> SysResult<usize> {
+    current_process().setsid()
+}
+
+/// Session of process `pid`, the caller if it's 0.
+pub fn sys_getsid(pid: usize) -> SysResult<usize> {
+    match pid {
+        0 => Ok(current_process().sid()),
+        pid => Ok(PROCESS_MANAGER.get_process(pid).ok_or(SysError::ESRCH)?.sid()),
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{
+    arch::{
+        mm::user_ptr::UserPtr,
+        process::{
+            PROCESS_MANAGER, current_process,
+            rlimit::{RLIM_NLIMITS, RLimit},
+        },
+    },
+    error::{SysError, SysResult},
+};
+
+pub fn sys_getrlimit(resource: usize, rlim: UserPtr<RLimit>) -> SysResult<usize> {
+    sys_prlimit64(0, resource, UserPtr::new(0), rlim)
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: UserPtr<RLimit>) -> SysResult<usize> {
+    sys_prlimit64(0, resource, rlim, UserPtr::new(0))
+}
+
+/// Get the limit of `resource` of process `pid` into `old_limit` and set it to `new_limit`,
+/// either may be null. `pid` is the caller if it's 0, otherwise it has to be one of its
+/// descendants, EPERM if it isn't.
+pub fn sys_prlimit64(
+    pid: usize,
+    resource: usize,
+    new_limit: UserPtr<RLimit>,
+    old_limit: UserPtr<RLimit>,
+) -> SysResult<usize> {
+    if resource >= RLIM_NLIMITS {
+        return Err(SysError::EINVAL);
+    }
+    let process = match pid {
+        0 => current_process(),
+        pid => PROCESS_MANAGER.get_process(pid).ok_or(SysError::ESRCH)?,
+    };
+    if !current_process().is_ancestor_of(&process) {
+        return Err(SysError::EPERM);
+    }
+    let old = match new_limit.is_null() {
+        true => process.rlimit(resource),
+        false => process.set_rlimit(resource, new_limit.read()?)?,
+    };
+    if !old_limit.is_null() {
+        old_limit.write(old)?;
+    }
+    Ok(0)
+}
@@ -0,0 +1,143 @@
+use alloc::sync::Arc;
+use core::mem::size_of;
+
+use crate::{
+    arch::{
+        cpu::{hart_id, online_harts},
+        mm::user_ptr::UserPtr,
+        process::{
+            NICE_MAX, NICE_MIN, PROCESS_MANAGER, RT_PRIORITY_MAX, SchedPolicy, ThreadControlBlock, current_thread,
+            suspend_current_and_run_next, update_sched_entity,
+        },
+    },
+    error::{SysError, SysResult},
+};
+
+const PRIO_PROCESS: usize = 0;
+
+/// `struct sched_param`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SchedParam {
+    sched_priority: i32,
+}
+
+/// Find the thread that `pid` refers to, which is a tid like Linux, 0 means the calling thread.
+fn find_thread(pid: usize) -> SysResult<Arc<ThreadControlBlock>> {
+    if pid == 0 {
+        return Ok(current_thread());
+    }
+    PROCESS_MANAGER.get_thread(pid).ok_or(SysError::ESRCH)
+}
+
+pub fn sys_sched_yield() -> SysResult<usize> {
+    suspend_current_and_run_next();
+    Ok(0)
+}
+
+pub fn sys_setpriority(which: usize, who: usize, prio: i32) -> SysResult<usize> {
+    if which != PRIO_PROCESS {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(who)?;
+    update_sched_entity(&thread, |se| se.nice = prio.clamp(NICE_MIN, NICE_MAX));
+    Ok(0)
+}
+
+/// Return `20 - nice` like Linux, so that a valid result is never negative.
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult<usize> {
+    if which != PRIO_PROCESS {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(who)?;
+    let nice = thread.inner_exclusive_access().sched_entity().nice;
+    Ok((20 - nice) as usize)
+}
+
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: UserPtr<SchedParam>) -> SysResult<usize> {
+    let policy = SchedPolicy::try_from(policy).map_err(|_| SysError::EINVAL)?;
+    if param.is_null() {
+        return Err(SysError::EINVAL);
+    }
+    let priority = param.read()?.sched_priority;
+    if !(priority_min(policy)..=priority_max(policy)).contains(&priority) {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(pid)?;
+    update_sched_entity(&thread, |se| {
+        se.policy = policy;
+        se.rt_priority = priority as u32;
+    });
+    Ok(0)
+}
+
+pub fn sys_sched_getscheduler(pid: usize) -> SysResult<usize> {
+    let thread = find_thread(pid)?;
+    let policy = thread.inner_exclusive_access().sched_entity().policy;
+    Ok(policy as usize)
+}
+
+pub fn sys_sched_get_priority_max(policy: usize) -> SysResult<usize> {
+    let policy = SchedPolicy::try_from(policy).map_err(|_| SysError::EINVAL)?;
+    Ok(priority_max(policy) as usize)
+}
+
+pub fn sys_sched_get_priority_min(policy: usize) -> SysResult<usize> {
+    let policy = SchedPolicy::try_from(policy).map_err(|_| SysError::EINVAL)?;
+    Ok(priority_min(policy) as usize)
+}
+
+/// Range of `sched_priority`, only real-time policies use a non-zero one.
+fn priority_min(policy: SchedPolicy) -> i32 {
+    if policy.is_realtime() { 1 } else { 0 }
+}
+
+fn priority_max(policy: SchedPolicy) -> i32 {
+    if policy.is_realtime() {
+        RT_PRIORITY_MAX as i32
+    } else {
+        0
+    }
+}
+
+/// Let the thread run only on the harts in `mask`, a `cpu_set_t` of `len` bytes, whose first
+/// word is all that's used as there are no more than 64 harts.
+/// The calling thread moves at once, another running thread moves when it leaves its hart.
+pub fn sys_sched_setaffinity(pid: usize, len: usize, mask: UserPtr<usize>) -> SysResult<usize> {
+    if len < size_of::<usize>() {
+        return Err(SysError::EINVAL);
+    }
+    let affinity = mask.read()? & online_harts();
+    if affinity == 0 {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(pid)?;
+    update_sched_entity(&thread, |se| se.affinity = affinity);
+    let moving = Arc::ptr_eq(&thread, &current_thread()) && affinity & (1 << hart_id()) == 0;
+    drop(thread);
+    if moving {
+        suspend_current_and_run_next();
+    }
+    Ok(0)
+}
+
+/// Write the harts the thread may run on to `mask` and return how many bytes are written.
+pub fn sys_sched_getaffinity(pid: usize, len: usize, mask: UserPtr<usize>) -> SysResult<usize> {
+    if len < size_of::<usize>() || len % size_of::<usize>() != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(pid)?;
+    let affinity = thread.inner_exclusive_access().sched_entity().affinity & online_harts();
+    mask.write(affinity)?;
+    Ok(size_of::<usize>())
+}
+
+pub fn sys_sched_getparam(pid: usize, param: UserPtr<SchedParam>) -> SysResult<usize> {
+    if param.is_null() {
+        return Err(SysError::EINVAL);
+    }
+    let thread = find_thread(pid)?;
+    let sched_priority = thread.inner_exclusive_access().sched_entity().rt_priority as i32;
+    param.write(SchedParam { sched_priority })?;
+    Ok(0)
+}
@@ -0,0 +1,167 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::mem::size_of;
+
+use super::time::nanosleep_until;
+use crate::{
+    arch::{
+        mm::user_ptr::UserPtr,
+        process::{
+            PROCESS_MANAGER, ProcessControlBlock, current_process, current_thread, futex_wait,
+            signal::{
+                NSIG, RestartBlock, SI_TKILL, SI_USER, SIGKILL, SIGSTOP, SigAction, SigInfo, SigSet, UNBLOCKABLE,
+                send_signal, send_signal_to_thread, set_sig_action, sigreturn,
+            },
+        },
+    },
+    error::{SysError, SysResult},
+};
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// Signal 0 is valid for kill and the like, which only check whether the target exists.
+fn check_signo(signo: usize) -> SysResult<()> {
+    match signo <= NSIG {
+        true => Ok(()),
+        false => Err(SysError::EINVAL),
+    }
+}
+
+/// The size of `sigset_t` of Linux, which the `sigsetsize` argument has to be.
+fn check_sigsetsize(sigsetsize: usize) -> SysResult<()> {
+    match sigsetsize == size_of::<SigSet>() {
+        true => Ok(()),
+        false => Err(SysError::EINVAL),
+    }
+}
+
+/// Send `signo` to process `pid`, to every process but initproc and the caller if `pid` is -1,
+/// or to every process in the group of the caller if it's 0 and in group `-pid` below -1.
+pub fn sys_kill(pid: isize, signo: usize) -> SysResult<usize> {
+    check_signo(signo)?;
+    let caller = current_process();
+    let targets: Vec<Arc<ProcessControlBlock>> = match pid {
+        0 => PROCESS_MANAGER.process_group(caller.pgid()),
+        // initproc is pid 1
+        -1 => PROCESS_MANAGER
+            .processes()
+            .into_iter()
+            .filter(|process| process.pid() != 1 && !Arc::ptr_eq(process, &caller))
+            .collect(),
+        ..0 => PROCESS_MANAGER.process_group(pid.unsigned_abs()),
+        pid => alloc::vec![PROCESS_MANAGER.get_process(pid as usize).ok_or(SysError::ESRCH)?],
+    };
+    if targets.is_empty() {
+        return Err(SysError::ESRCH);
+    }
+    if signo != 0 {
+        for target in targets.iter() {
+            send_signal(target, SigInfo::user(signo, SI_USER, caller.pid()));
+        }
+    }
+    Ok(0)
+}
+
+/// Send `signo` to thread `tid`.
+pub fn sys_tkill(tid: usize, signo: usize) -> SysResult<usize> {
+    check_signo(signo)?;
+    let thread = PROCESS_MANAGER.get_thread(tid).ok_or(SysError::ESRCH)?;
+    if signo != 0 {
+        send_signal_to_thread(&thread, SigInfo::user(signo, SI_TKILL, current_process().pid()));
+    }
+    Ok(0)
+}
+
+/// Send `signo` to thread `tid`, which has to be in process `tgid`.
+pub fn sys_tgkill(tgid: usize, tid: usize, signo: usize) -> SysResult<usize> {
+    let thread = PROCESS_MANAGER.get_thread(tid).ok_or(SysError::ESRCH)?;
+    if thread.process().pid() != tgid {
+        return Err(SysError::ESRCH);
+    }
+    sys_tkill(tid, signo)
+}
+
+pub fn sys_rt_sigaction(
+    signo: usize,
+    act: UserPtr<SigAction>,
+    oldact: UserPtr<SigAction>,
+    sigsetsize: usize,
+) -> SysResult<usize> {
+    check_sigsetsize(sigsetsize)?;
+    if signo == 0 || signo > NSIG {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_process();
+    let old = match act.is_null() {
+        true => process.inner_exclusive_access().sig_actions[signo - 1],
+        false => {
+            if signo == SIGKILL || signo == SIGSTOP {
+                return Err(SysError::EINVAL);
+            }
+            let mut action = act.read()?;
+            action.mask = action.mask.difference(UNBLOCKABLE);
+            set_sig_action(&process, signo, action)
+        },
+    };
+    if !oldact.is_null() {
+        oldact.write(old)?;
+    }
+    Ok(0)
+}
+
+pub fn sys_rt_sigprocmask(
+    how: usize,
+    set: UserPtr<SigSet>,
+    oldset: UserPtr<SigSet>,
+    sigsetsize: usize,
+) -> SysResult<usize> {
+    check_sigsetsize(sigsetsize)?;
+    let thread = current_thread();
+    let old = thread.inner_exclusive_access().sig_mask();
+    if !set.is_null() {
+        let set = set.read()?;
+        let mask = match how {
+            SIG_BLOCK => old.union(set),
+            SIG_UNBLOCK => old.difference(set),
+            SIG_SETMASK => set,
+            _ => return Err(SysError::EINVAL),
+        };
+        // signals unblocked here are taken on the way back to user
+        thread
+            .inner_exclusive_access()
+            .set_sig_mask(mask.difference(UNBLOCKABLE));
+    }
+    if !oldset.is_null() {
+        oldset.write(old)?;
+    }
+    Ok(0)
+}
+
+/// Signals pending for the calling thread but blocked.
+pub fn sys_rt_sigpending(set: UserPtr<SigSet>, sigsetsize: usize) -> SysResult<usize> {
+    check_sigsetsize(sigsetsize)?;
+    let thread = current_thread();
+    let thread_inner = thread.inner_exclusive_access();
+    let mask = thread_inner.sig_mask();
+    let pending = thread_inner.sig_pending().set();
+    drop(thread_inner);
+    let pending = pending.union(thread.process().inner_exclusive_access().sig_pending.set());
+    set.write(pending.intersection(mask))?;
+    Ok(0)
+}
+
+/// Carry on with the syscall interrupted before the signals were handled, see
+/// `SysError::ERESTART_RESTARTBLOCK`. EINTR if there's nothing to carry on with.
+pub fn sys_restart_syscall() -> SysResult<usize> {
+    let restart_block = current_thread().inner_exclusive_access().take_restart_block();
+    match restart_block {
+        Some(RestartBlock::Nanosleep { deadline, rem }) => nanosleep_until(deadline, rem),
+        Some(RestartBlock::FutexWait { uaddr, val, deadline }) => futex_wait(uaddr, val, Some(deadline)),
+        None => Err(SysError::EINTR),
+    }
+}
+
+pub fn sys_rt_sigreturn() -> SysResult<usize> {
+    sigreturn()
+}
@@ -0,0 +1,31 @@
+use crate::{
+    arch::{
+        mm::user_ptr::UserPtr,
+        process::{WaitResult, current_thread, signal::RestartBlock, sleep_until},
+        timer::{TimeSpec, get_time},
+    },
+    error::{SysError, SysResult},
+};
+
+/// Sleep for `req`, the time left is written to `rem` if it's interrupted by a handler.
+/// Like Linux, it's never restarted after a handler whether it has `SA_RESTART` or not, and
+/// otherwise it sleeps on for the time left, e.g. after being stopped.
+pub fn sys_nanosleep(req: UserPtr<TimeSpec>, rem: UserPtr<TimeSpec>) -> SysResult<usize> {
+    let deadline = get_time().saturating_add(req.read()?.to_cycles()?);
+    nanosleep_until(deadline, rem)
+}
+
+pub fn nanosleep_until(deadline: usize, rem: UserPtr<TimeSpec>) -> SysResult<usize> {
+    match sleep_until(deadline) {
+        WaitResult::Interrupted => {
+            if !rem.is_null() {
+                rem.write(TimeSpec::from_cycles(deadline.saturating_sub(get_time())))?;
+            }
+            current_thread()
+                .inner_exclusive_access()
+                .set_restart_block(RestartBlock::Nanosleep { deadline, rem });
+            Err(SysError::ERESTART_RESTARTBLOCK)
+        },
+        _ => Ok(0),
+    }
+}
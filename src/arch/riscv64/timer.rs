@@ -1,8 +1,11 @@
 use riscv::register::time;
 
-use crate::arch::{config::CLOCK_FREQ, sbi::set_timer};
+use crate::{
+    arch::{config::CLOCK_FREQ, sbi::set_timer},
+    error::{SysError, SysResult},
+};
 
-const TICKS_PER_SEC: usize = 100;
+pub const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
 #[allow(unused)]
 const USEC_PER_SEC: usize = 1000000;
@@ -17,6 +20,34 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// `struct timespec` of Linux
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_cycles(cycles: usize) -> Self {
+        Self {
+            tv_sec: cycles / CLOCK_FREQ,
+            tv_nsec: cycles % CLOCK_FREQ * NSEC_PER_SEC / CLOCK_FREQ,
+        }
+    }
+
+    /// Convert to timer cycles, EINVAL if `tv_nsec` is out of range like Linux.
+    pub fn to_cycles(&self) -> SysResult<usize> {
+        if self.tv_nsec >= NSEC_PER_SEC {
+            return Err(SysError::EINVAL);
+        }
+        Ok(self
+            .tv_sec
+            .saturating_mul(CLOCK_FREQ)
+            .saturating_add(self.tv_nsec * CLOCK_FREQ / NSEC_PER_SEC))
+    }
+}
+
 /// set the next timer interrupt
 pub fn set_next_trigger() {
     const TIME_SLICE: usize = CLOCK_FREQ / TICKS_PER_SEC;
@@ -1,13 +1,25 @@
+use core::fmt;
+
 use riscv::register::sstatus::{self, SPP, Sstatus};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub sstatus: Sstatus,
     pub sepc: usize,
     pub kernel_sp: usize,
     pub trap_handler: usize,
+    /// `tp` of the hart which returned to user, points to its `CpuContext`
+    pub kernel_tp: usize,
+}
+
+/// Context of a trap taken in kernel mode, saved on the kernel stack by `__trap_from_kernel`.
+#[repr(C)]
+pub struct KernelTrapContext {
+    pub x: [usize; 32],
+    pub sstatus: usize,
+    pub sepc: usize,
 }
 
 impl TrapContext {
@@ -25,8 +37,28 @@ impl TrapContext {
             sepc: entry,
             kernel_sp,
             trap_handler,
+            kernel_tp: 0,
         };
         cx.set_sp(sp);
         cx
     }
 }
+
+impl fmt::Debug for KernelTrapContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [&str; 32] = [
+            "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5", "a6",
+            "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+        ];
+        writeln!(f, "sepc: {:#018x}, sstatus: {:#018x}", self.sepc, self.sstatus)?;
+        for (i, name) in NAMES.iter().enumerate() {
+            write!(f, "{:>4}: {:#018x}", name, self.x[i])?;
+            if i % 4 == 3 {
+                writeln!(f)?;
+            } else {
+                write!(f, "  ")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use core::{mem::size_of, slice};
+
+/// An entry of the exception table, emitted into `.extable` by the user-copy routines.
+/// A fault at `insn` resumes at `fixup`.
+#[repr(C)]
+struct ExceptionTableEntry {
+    insn: usize,
+    fixup: usize,
+}
+
+/// Find the fixup address of the faulting instruction at `addr`.
+pub fn search_exception_table(addr: usize) -> Option<usize> {
+    unsafe extern "C" {
+        fn sextable();
+        fn eextable();
+    }
+    let table = unsafe {
+        slice::from_raw_parts(
+            sextable as usize as *const ExceptionTableEntry,
+            (eextable as usize - sextable as usize) / size_of::<ExceptionTableEntry>(),
+        )
+    };
+    table.iter().find(|entry| entry.insn == addr).map(|entry| entry.fixup)
+}
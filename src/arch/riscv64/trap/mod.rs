@@ -1,18 +1,42 @@
 pub mod context;
+mod extable;
 
-use core::arch::global_asm;
+use core::arch::{asm, global_asm};
 
-use riscv::register::{mtvec::TrapMode, scause, sepc, sie, stval, stvec};
+use log::warn;
+use riscv::register::{
+    mtvec::TrapMode,
+    scause::{self, Exception, Interrupt, Trap},
+    sepc, sie, sip, sstatus, stval, stvec,
+};
 
-use crate::arch::{
-    config::CLOCK_FREQ,
-    mm::address::{VirtAddr, VirtPageNum},
+use self::context::KernelTrapContext;
+use crate::{
+    arch::{
+        config::{MAX_HARTS, USER_SPACE_END},
+        cpu::local_cpu_context,
+        mm::{address::VirtAddr, map_area::MapPermission},
+        process::{
+            check_timers, current_process, current_thread, current_trap_cx, current_trap_cx_user_va,
+            current_user_token, need_resched, poll_console, scheduler_tick,
+            signal::{
+                BUS_ADRALN, ILL_ILLOPC, ILL_ILLTRP, SEGV_ACCERR, SEGV_MAPERR, SIGBUS, SIGILL, SIGSEGV, SIGTRAP,
+                SigInfo, TRAP_BRKPT, force_signal, handle_signals,
+            },
+            suspend_current_and_run_next,
+        },
+        syscall::{SYSCALL_RT_SIGRETURN, syscall},
+        timer,
+    },
+    error::SysError,
 };
 
-global_asm!(include_str!("trap.asm"));
+global_asm!(include_str!("trap.asm"), MAX_HARTS = const MAX_HARTS);
 
 unsafe extern "C" {
     fn __trap_from_user();
+    fn __return_to_user(trap_cx: usize, user_token: usize) -> !;
+    fn __trap_from_kernel();
 }
 // when we call this function, we are in kernel mode
 // we will set the trap entry to kernel_trap
@@ -22,7 +46,7 @@ pub fn init() {
 
 fn set_kernel_trap_entry() {
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__trap_from_kernel as usize, TrapMode::Direct);
     }
 }
 
@@ -39,19 +63,204 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// enable software interrupt in sie CSR, used as inter-processor interrupt
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
+
+/// allow interrupts to be taken in kernel mode
+pub fn enable_kernel_interrupt() {
+    unsafe {
+        sstatus::set_sie();
+    }
+}
+
+/// forbid interrupts to be taken in kernel mode
+pub fn disable_kernel_interrupt() {
+    unsafe {
+        sstatus::clear_sie();
+    }
+}
+
+/// `scause` of a misaligned load.
+const LOAD_MISALIGNED: usize = 4;
+
+/// Handle a trap from user mode, jumped to by `__trap_from_user` on the kernel stack.
 #[unsafe(no_mangle)]
-pub fn trap_from_kernel() -> ! {
+pub fn trap_handler() -> ! {
+    set_kernel_trap_entry();
+    let scause = scause::read();
     let stval = stval::read();
-    let sepc = sepc::read();
-    let stval_vpn = VirtPageNum::from(VirtAddr::from(stval));
-    let sepc_vpn = VirtPageNum::from(VirtAddr::from(sepc));
+    // a0 of the syscall made, in case it's interrupted by a signal and has to be restarted
+    let mut syscall_a0 = None;
+    match scause.cause() {
+        Trap::Exception(Exception::UserEnvCall) => {
+            let mut cx = current_trap_cx();
+            let (syscall_id, a0) = (cx.x[17], cx.x[10]);
+            // jump to next instruction anyway
+            cx.sepc += 4;
+            let result = syscall(syscall_id, [a0, cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]]);
+            // cx is changed during sys_exec, so we have to call it again
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
+            // rt_sigreturn restores a0 of user, which is not an error even if it looks like one
+            if syscall_id != SYSCALL_RT_SIGRETURN {
+                syscall_a0 = Some(a0);
+            }
+        },
+        // e.g. the first touch of a page or the first write to a copy-on-write page, the faulting
+        // instruction is retried. A fault out of the user space, which may not even be canonical,
+        // is left to SIGSEGV below.
+        Trap::Exception(
+            exception @ (Exception::LoadPageFault | Exception::StorePageFault | Exception::InstructionPageFault),
+        ) if stval < USER_SPACE_END
+            && current_process()
+                .inner_exclusive_access()
+                .handle_page_fault(VirtAddr(stval), fault_access(exception), current_trap_cx().x[2])
+                .is_ok() => {},
+        Trap::Exception(
+            Exception::StoreFault
+            | Exception::StorePageFault
+            | Exception::InstructionFault
+            | Exception::InstructionPageFault
+            | Exception::LoadFault
+            | Exception::LoadPageFault,
+        ) => {
+            // an address in no area isn't mapped at all, otherwise the access isn't allowed
+            let in_area = stval < USER_SPACE_END
+                && current_process()
+                    .inner_exclusive_access()
+                    .memory
+                    .find_area(VirtAddr(stval).floor())
+                    .is_some();
+            let code = if in_area { SEGV_ACCERR } else { SEGV_MAPERR };
+            force_signal(SigInfo::fault(SIGSEGV, code, stval));
+        },
+        Trap::Exception(Exception::InstructionMisaligned | Exception::StoreMisaligned) => {
+            force_signal(SigInfo::fault(SIGBUS, BUS_ADRALN, stval))
+        },
+        // load address misaligned, which the crate has no name for
+        Trap::Exception(Exception::Unknown) if scause.code() == LOAD_MISALIGNED => {
+            force_signal(SigInfo::fault(SIGBUS, BUS_ADRALN, stval))
+        },
+        Trap::Exception(Exception::IllegalInstruction) => {
+            force_signal(SigInfo::fault(SIGILL, ILL_ILLOPC, current_trap_cx().sepc));
+        },
+        Trap::Exception(Exception::Breakpoint) => {
+            force_signal(SigInfo::fault(SIGTRAP, TRAP_BRKPT, current_trap_cx().sepc));
+        },
+        // any other exception is taken from user mode as well, the program can't go on from there
+        Trap::Exception(_) => {
+            force_signal(SigInfo::fault(SIGILL, ILL_ILLTRP, current_trap_cx().sepc));
+        },
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            timer::set_next_trigger();
+            check_timers();
+            poll_console();
+            // the tick is charged to whoever was running in user mode
+            current_process().account_tick();
+            if scheduler_tick() {
+                suspend_current_and_run_next();
+            }
+        },
+        // e.g. a real-time thread is queued here by another hart, see below
+        Trap::Interrupt(Interrupt::SupervisorSoft) => unsafe { sip::clear_ssoft() },
+        _ => {
+            panic!("Unsupported trap {:?}, stval = {:#x}!", scause.cause(), stval);
+        },
+    }
+    // e.g. a real-time thread is woken up by this syscall
+    if need_resched() {
+        suspend_current_and_run_next();
+    }
+    // signals may be sent while running in the kernel, e.g. by this syscall, or exit_group may be
+    // called by another thread, whose exit code is taken instead
+    handle_signals(syscall_a0);
+    trap_return();
+}
+
+/// The access a page fault is taken on, in terms of the permission it needs.
+fn fault_access(exception: Exception) -> MapPermission {
+    match exception {
+        Exception::StorePageFault => MapPermission::W,
+        Exception::InstructionPageFault => MapPermission::X,
+        _ => MapPermission::R,
+    }
+}
+
+/// Return to user mode through `__return_to_user`.
+#[unsafe(no_mangle)]
+pub fn trap_return() -> ! {
+    // an interrupt must not be taken in kernel mode once stvec points to the user trap entry,
+    // sret will re-enable interrupts in user mode anyway
+    disable_kernel_interrupt();
+    set_user_trap_entry();
+    current_trap_cx().kernel_sp = current_thread().kstack_top();
+    let trap_cx_ptr = current_trap_cx_user_va();
+    let user_satp = current_user_token();
+    unsafe {
+        // user code may have just been loaded
+        asm!("fence.i");
+        __return_to_user(trap_cx_ptr, user_satp)
+    }
+}
+
+/// Report a kernel stack overflow, called by `__trap_from_kernel` on a stack of its own with the
+/// overflowed `sp`.
+#[unsafe(no_mangle)]
+pub fn kernel_stack_overflow(sp: usize) -> ! {
+    let tid = local_cpu_context().current().map(|thread| thread.tid());
+    panic!(
+        "kernel stack overflow in tid {}, sp = {:#x}, sepc = {:#x}, stval = {:#x}",
+        tid.map_or(-1, |tid| tid as isize),
+        sp,
+        sepc::read(),
+        stval::read()
+    );
+}
+
+/// Handle a trap from kernel mode, called by `__trap_from_kernel`.
+/// Interrupts are handled and the interrupted code is resumed when returning.
+/// Faults in user-copy routines resume at their fixup with -EFAULT in a0, other exceptions are
+/// fatal.
+#[unsafe(no_mangle)]
+pub fn trap_from_kernel(cx: &mut KernelTrapContext) {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // kernel code is not preemptible, threads are only switched on ticks from user mode
+            timer::set_next_trigger();
+            return;
+        },
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // nothing to do but acknowledge it, the hart is woken up already
+            unsafe { sip::clear_ssoft() };
+            return;
+        },
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            // no device is driven by interrupts yet, so seie is never set
+            warn!("unexpected external interrupt in kernel");
+            return;
+        },
+        Trap::Exception(
+            Exception::LoadFault | Exception::LoadPageFault | Exception::StoreFault | Exception::StorePageFault,
+        ) => {
+            if let Some(fixup) = extable::search_exception_table(cx.sepc) {
+                cx.sepc = fixup;
+                cx.x[10] = SysError::EFAULT.code() as usize;
+                return;
+            }
+        },
+        _ => {},
+    }
     panic!(
-        "stval = {:#x}(vpn {}), sepc = {:#x}(vpn{}),
-        a trap {:?} from kernel!",
+        "a trap {:?} from kernel, stval = {:#x}, sepc = {:#x}\n{:?}",
+        scause.cause(),
         stval,
-        stval_vpn.0,
-        sepc,
-        sepc_vpn.0,
-        scause::read().cause()
+        cx.sepc,
+        cx
     );
 }
@@ -11,6 +11,7 @@ FP_START = 51
 .section .text
     .globl __trap_from_user
     .globl __return_to_user
+    .globl __trap_from_kernel
     .align 2
 
 # user trap into kernel
@@ -34,8 +35,10 @@ __trap_from_user:
     csrr t2, sscratch
     sd t2, 2*8(sp)
 
-    # load trap_handler into t0 
+    # load trap_handler into t0
     ld t0, 35*8(sp)
+    # restore kernel tp, which points to the CpuContext of this hart
+    ld tp, 36*8(sp)
     # move to kernel sp
     ld sp, 34*8(sp)
     jr t0
@@ -47,13 +50,66 @@ __return_to_user:
     mv sp, a0
 
     # now sp points to TrapContext in user space, start restoring based on it
+    # save kernel tp, we will need it when trapping back
+    sd tp, 36*8(sp)
     # restore sstatus/sepc
     ld t0, 32*8(sp)
     ld t1, 33*8(sp)
     csrw sstatus, t0
     csrw sepc, t1
 
+    # restore general purpose registers except x0/sp
+    ld x1, 1*8(sp)
+    .set n, 3
+    .rept 29
+        LOAD_GP %n
+        .set n, n+1
+    .endr
+    # back to user stack
+    ld sp, 2*8(sp)
+    sret
+
+# kernel trap, save KernelTrapContext on the current kernel stack
+    .align 2
+__trap_from_kernel:
+    # sscratch is free in kernel mode, borrow it to check where the context goes first
+    csrw sscratch, t0
+    # it's in the kernel stack area, i.e. the last 1 GiB
+    addi t0, sp, -34*8
+    srai t0, t0, 30
+    addi t0, t0, 1
+    bnez t0, 1f
+    # and in the lower half of a slot, which is the guard below a stack
+    addi t0, sp, -34*8
+    srli t0, t0, 14
+    andi t0, t0, 1
+    beqz t0, __kernel_stack_overflow
+1:
+    csrr t0, sscratch
+    addi sp, sp, -34*8
+    sd x1, 1*8(sp)
+    .set n, 3
+    .rept 29
+        SAVE_GP %n
+        .set n, n+1
+    .endr
+    # save sp before trapping
+    addi t0, sp, 34*8
+    sd t0, 2*8(sp)
+    csrr t0, sstatus
+    csrr t1, sepc
+    sd t0, 32*8(sp)
+    sd t1, 33*8(sp)
+
+    mv a0, sp
+    call trap_from_kernel
+
+    ld t0, 32*8(sp)
+    ld t1, 33*8(sp)
+    csrw sstatus, t0
+    csrw sepc, t1
     # restore general purpose registers except x0/sp/tp
+    # tp belongs to the hart, not to the interrupted code
     ld x1, 1*8(sp)
     ld x3, 3*8(sp)
     .set n, 5
@@ -61,6 +117,21 @@ __return_to_user:
         LOAD_GP %n
         .set n, n+1
     .endr
-    # back to user stack
-    ld sp, 2*8(sp)
+    addi sp, sp, 34*8
     sret
+
+# nothing can be saved on an overflowed stack, report it on the overflow stack of this hart
+__kernel_stack_overflow:
+    mv a0, sp
+    # hart id is the first field of the CpuContext that tp points to
+    ld t0, 0(tp)
+    addi t0, t0, 1
+    slli t0, t0, 14
+    la sp, overflow_stack_bottom
+    add sp, sp, t0
+    call kernel_stack_overflow
+
+    .section .bss
+    .align 12
+overflow_stack_bottom:
+    .space 4096 * 4 * {MAX_HARTS}
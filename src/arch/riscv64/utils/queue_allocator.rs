@@ -13,14 +13,32 @@ struct QueueAllocatorInner {
 
 impl QueueAllocator {
     pub fn new() -> Self {
+        Self::new_from(0)
+    }
+
+    /// Allocate ids from `start`.
+    pub fn new_from(start: usize) -> Self {
         QueueAllocator {
             inner: Mutex::new(QueueAllocatorInner {
-                current: 0,
+                current: start,
                 recycled: Vec::new(),
             }),
         }
     }
 
+    /// Allocate `id` first, then the ids below it, and then from `id + 1`.
+    pub fn new_first(id: usize) -> Self {
+        // popped from the back
+        let mut recycled: Vec<usize> = (0..id).rev().collect();
+        recycled.push(id);
+        QueueAllocator {
+            inner: Mutex::new(QueueAllocatorInner {
+                current: id + 1,
+                recycled,
+            }),
+        }
+    }
+
     pub fn alloc(&self) -> usize {
         let mut inner = self.inner.lock();
         if let Some(id) = inner.recycled.pop() {
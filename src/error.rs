@@ -0,0 +1,74 @@
+/// Kernel error, each variant maps onto a Linux errno value.
+#[allow(unused, non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(isize)]
+pub enum SysError {
+    /// Operation not permitted
+    EPERM = 1,
+    /// No such file or directory
+    ENOENT = 2,
+    /// No such process
+    ESRCH = 3,
+    /// Interrupted system call
+    EINTR = 4,
+    /// I/O error
+    EIO = 5,
+    /// Argument list too long
+    E2BIG = 7,
+    /// Exec format error
+    ENOEXEC = 8,
+    /// Bad file number
+    EBADF = 9,
+    /// No child processes
+    ECHILD = 10,
+    /// Try again
+    EAGAIN = 11,
+    /// Out of memory
+    ENOMEM = 12,
+    /// Permission denied
+    EACCES = 13,
+    /// Bad address
+    EFAULT = 14,
+    /// Device or resource busy
+    EBUSY = 16,
+    /// File exists
+    EEXIST = 17,
+    /// Invalid argument
+    EINVAL = 22,
+    /// Too many open files
+    EMFILE = 24,
+    /// Not a typewriter
+    ENOTTY = 25,
+    /// Math result not representable
+    ERANGE = 34,
+    /// Resource deadlock would occur
+    EDEADLK = 35,
+    /// File name too long
+    ENAMETOOLONG = 36,
+    /// Function not implemented
+    ENOSYS = 38,
+    /// Operation not supported on transport endpoint
+    EOPNOTSUPP = 95,
+    /// Connection timed out
+    ETIMEDOUT = 110,
+    /// Returned by a blocking syscall interrupted by a signal. It's restarted if there's no
+    /// handler to run or the handler has `SA_RESTART`, otherwise it returns EINTR.
+    /// Like the ones below, it's never seen by user.
+    ERESTARTSYS = 512,
+    /// Like `ERESTARTSYS`, but the syscall is restarted whether there's a handler or not
+    ERESTARTNOINTR = 513,
+    /// Like `ERESTARTSYS`, but the syscall is only restarted if there's no handler to run
+    ERESTARTNOHAND = 514,
+    /// Like `ERESTARTNOHAND`, but it's restarted as `restart_syscall`, which carries on with the
+    /// restart block of the thread, e.g. a sleep with the time left
+    ERESTART_RESTARTBLOCK = 516,
+}
+
+pub type SysResult<T> = Result<T, SysError>;
+
+impl SysError {
+    /// The negative errno returned to user.
+    pub fn code(self) -> isize {
+        -(self as isize)
+    }
+}
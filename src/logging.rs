@@ -38,6 +38,13 @@ pub fn print(args: fmt::Arguments) {
     console::put_fmt(args);
 }
 
+/// Print bytes as they are, e.g. what the user writes, whose characters may be split between
+/// writes.
+pub fn print_bytes(bytes: &[u8]) {
+    let _guard = PRINT_MUTEX.lock();
+    console::put_bytes(bytes);
+}
+
 struct ArtemosLogger;
 
 impl Log for ArtemosLogger {
@@ -6,6 +6,7 @@ use core::arch::global_asm;
 
 extern crate alloc;
 
+mod error;
 mod loader;
 mod logging;
 mod panic;
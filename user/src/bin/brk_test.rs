@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+//! The program break: growing and shrinking it, where it can't go, and the heap growing on it.
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+
+use user_lib::{
+    MAP_ANONYMOUS, MAP_FIXED_NOREPLACE, MAP_PRIVATE, PROT_READ, PROT_WRITE, SIGSEGV, brk, mmap, munmap,
+    testing::{in_child, load, store},
+};
+
+const PAGE_SIZE: usize = 4096;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // nothing may allocate between the calls, as the heap moves the break as well
+    let base = brk(0);
+    let top = base + 3 * PAGE_SIZE + 5;
+    assert_eq!(brk(top), top);
+    for addr in (base..top).step_by(PAGE_SIZE / 2).chain([top - 1]) {
+        assert_eq!(load(addr), 0);
+        store(addr, 0xa5);
+    }
+
+    // the pages above the break are gone
+    let page = base.next_multiple_of(PAGE_SIZE) + PAGE_SIZE;
+    assert_eq!(brk(base), base);
+    let status = in_child(|| {
+        load(page);
+    });
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+    assert_eq!(brk(top), top);
+    assert_eq!(load(page), 0);
+
+    // out of the user space and below the heap, the break stays
+    assert_eq!(brk(usize::MAX), top);
+    assert_eq!(brk(1), top);
+
+    // nor does it grow over a mapping
+    let fixed = top.next_multiple_of(PAGE_SIZE) + PAGE_SIZE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE;
+    assert_eq!(
+        mmap(fixed, PAGE_SIZE, PROT_READ | PROT_WRITE, flags, usize::MAX, 0),
+        fixed as isize
+    );
+    assert_eq!(brk(fixed + PAGE_SIZE), top);
+    assert_eq!(munmap(fixed, PAGE_SIZE), 0);
+    assert_eq!(brk(fixed + PAGE_SIZE), fixed + PAGE_SIZE);
+    assert_eq!(brk(base), base);
+
+    // far more than the heap had at first
+    let big = vec![7u8; 1 << 20];
+    assert!(big.iter().all(|&byte| byte == 7));
+    assert!(brk(0) > base);
+
+    println!("brk_test passed!");
+    0
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+//! Processes made by clone: a fork by a thread other than the main one, and vfork blocking the
+//! parent until the child is done with it.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use user_lib::{
+    WNOHANG, exec, exit, exit_thread, fork, futex_wait, futex_wake, gettid, sleep, thread_create, vfork, waitpid,
+    waitpid_options,
+};
+
+/// wait status of the child forked by the thread, 0 until it's reaped
+static FORKED_STATUS: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn fork_from_thread(_arg: usize) -> ! {
+    let tid = gettid();
+    let pid = fork();
+    if pid == 0 {
+        // the only thread of the child keeps the tid
+        exit(if gettid() == tid { 5 } else { 1 });
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    FORKED_STATUS.store(status as u32, Ordering::SeqCst);
+    futex_wake(&FORKED_STATUS, 1);
+    exit_thread(0)
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert!(thread_create(fork_from_thread, 0) > 0);
+    while FORKED_STATUS.load(Ordering::SeqCst) == 0 {
+        futex_wait(&FORKED_STATUS, 0);
+    }
+    assert_eq!(FORKED_STATUS.load(Ordering::SeqCst), 5 << 8);
+
+    // the parent goes on once the child has exited, so it's there to reap at once, even though a
+    // failed exec came first
+    let pid = vfork();
+    if pid == 0 {
+        assert!(
+            exec("no_such_program\0", &[core::ptr::null::<u8>()], &[
+                core::ptr::null::<u8>()
+            ]) < 0
+        );
+        sleep(50);
+        exit(3);
+    }
+    assert!(pid > 0);
+    let mut status = 0;
+    assert_eq!(waitpid_options(pid, &mut status, WNOHANG), pid);
+    assert_eq!(status, 3 << 8);
+
+    println!("clone_test passed!");
+    0
+}
@@ -0,0 +1,120 @@
+#![no_std]
+#![no_main]
+
+//! Priority inversion: a low priority thread holds two PI mutexes which two high priority threads
+//! need, while a medium priority thread is ready to run. Lent the priorities of the high ones, the
+//! low one unlocks both before the medium one runs, still lent the priority of the waiter of the
+//! second one after handing over the first. So both high ones finish first.
+//! The threads share one hart, on which the medium one would keep the low one from running.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::{
+    hint::black_box,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use user_lib::{
+    PiMutex, SCHED_FIFO, exit_thread, futex_wait, futex_wake, sched_getaffinity, sched_setaffinity, sched_setscheduler,
+    thread_create,
+};
+
+const EAGAIN: isize = -11;
+const WORK: usize = 1 << 24;
+
+static OUTER: PiMutex = PiMutex::new();
+static INNER: PiMutex = PiMutex::new();
+/// set once the low priority thread holds both mutexes
+static LOCKED: AtomicU32 = AtomicU32::new(0);
+static FINISHED: AtomicU32 = AtomicU32::new(0);
+static OUTER_FINISHED_AT: AtomicU32 = AtomicU32::new(0);
+static INNER_FINISHED_AT: AtomicU32 = AtomicU32::new(0);
+static MEDIUM_FINISHED_AT: AtomicU32 = AtomicU32::new(0);
+
+fn busy() {
+    for i in 0..WORK {
+        black_box(i);
+    }
+}
+
+fn finish(finished_at: Option<&AtomicU32>) -> ! {
+    let order = FINISHED.fetch_add(1, Ordering::SeqCst);
+    if let Some(finished_at) = finished_at {
+        finished_at.store(order, Ordering::SeqCst);
+    }
+    futex_wake(&FINISHED, 1);
+    exit_thread(0)
+}
+
+extern "C" fn low(_: usize) -> ! {
+    OUTER.lock();
+    INNER.lock();
+    LOCKED.store(1, Ordering::SeqCst);
+    futex_wake(&LOCKED, 1);
+    busy();
+    INNER.unlock();
+    busy();
+    OUTER.unlock();
+    finish(None)
+}
+
+extern "C" fn medium(_: usize) -> ! {
+    busy();
+    finish(Some(&MEDIUM_FINISHED_AT))
+}
+
+extern "C" fn high_outer(_: usize) -> ! {
+    OUTER.lock();
+    OUTER.unlock();
+    finish(Some(&OUTER_FINISHED_AT))
+}
+
+extern "C" fn high_inner(_: usize) -> ! {
+    INNER.lock();
+    INNER.unlock();
+    finish(Some(&INNER_FINISHED_AT))
+}
+
+/// Start a thread with `priority`, which can't run before the caller blocks.
+fn spawn(entry: extern "C" fn(usize) -> !, priority: i32) {
+    let tid = thread_create(entry, 0);
+    assert!(tid > 0);
+    assert_eq!(sched_setscheduler(tid as usize, SCHED_FIFO, priority), 0);
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // the word has changed before blocking
+    assert_eq!(futex_wait(&LOCKED, 1), EAGAIN);
+    // the threads inherit it
+    let harts = sched_getaffinity(0);
+    assert!(harts > 0);
+    assert_eq!(sched_setaffinity(0, (harts & -harts) as usize), 0);
+    assert_eq!(sched_setscheduler(0, SCHED_FIFO, 90), 0);
+    spawn(low, 10);
+    while LOCKED.load(Ordering::SeqCst) == 0 {
+        futex_wait(&LOCKED, 0);
+    }
+    spawn(medium, 50);
+    spawn(high_inner, 70);
+    spawn(high_outer, 60);
+    loop {
+        let finished = FINISHED.load(Ordering::SeqCst);
+        if finished == 4 {
+            break;
+        }
+        futex_wait(&FINISHED, finished);
+    }
+    let medium_finished_at = MEDIUM_FINISHED_AT.load(Ordering::SeqCst);
+    assert!(
+        INNER_FINISHED_AT.load(Ordering::SeqCst) < medium_finished_at,
+        "priority inversion: the medium priority thread ran before the high one"
+    );
+    assert!(
+        OUTER_FINISHED_AT.load(Ordering::SeqCst) < medium_finished_at,
+        "priority inversion: the boost for the outer mutex was lost with the inner one"
+    );
+    println!("futex_pi_test passed!");
+    0
+}
@@ -3,7 +3,7 @@
 
 extern crate user_lib;
 
-use user_lib::{exec, fork, wait};
+use user_lib::{exec, fork, println, wait, yield_};
 
 #[unsafe(no_mangle)]
 fn main() -> i32 {
@@ -12,7 +12,15 @@ fn main() -> i32 {
     } else {
         loop {
             let mut exit_code: i32 = 0;
-            let _pid = wait(&mut exit_code);
+            let pid = wait(&mut exit_code);
+            if pid < 0 {
+                yield_();
+                continue;
+            }
+            println!(
+                "[initproc] released a zombie process, pid={}, exit_status={:#x}",
+                pid, exit_code
+            );
         }
     }
     0
@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+//! Process groups, sessions and job control: stopped and continued jobs seen by wait4, a
+//! background job stopped by reading the console, and the foreground group of the console.
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    SIG_IGN, SIGCONT, SIGINT, SIGKILL, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU, SigAction, WCONTINUED, WUNTRACED, exit,
+    fork, getpgid, getpid, getsid, kill, read, setpgid, setsid, sigaction, tcgetpgrp, tcsetpgrp,
+    testing::initproc_reaps, waitpid, waitpid_options,
+};
+
+const ENOTTY: isize = -25;
+
+/// Start a child running `job` in a group of its own, like a shell does for a job.
+fn spawn_job(job: fn() -> i32) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setpgid(0, 0), 0);
+        exit(job());
+    }
+    // either one may run first
+    assert_eq!(setpgid(pid as usize, pid as usize), 0);
+    pid as usize
+}
+
+fn stop_itself() -> i32 {
+    kill(getpid(), SIGTSTP);
+    7
+}
+
+fn read_console() -> i32 {
+    let mut buf = [0u8; 1];
+    read(0, &mut buf);
+    unreachable!("a background job read the console");
+}
+
+fn new_session() -> i32 {
+    let pid = getpid();
+    assert_eq!(setsid(), pid);
+    assert_eq!(getsid(0), pid);
+    assert_eq!(getpgid(0), pid);
+    // left the controlling terminal behind
+    assert_eq!(tcgetpgrp(), ENOTTY);
+    0
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let pgid = getpgid(0) as usize;
+    let mut status = 0;
+
+    let job = spawn_job(stop_itself);
+    assert_eq!(getpgid(job), job as isize);
+    assert_eq!(getsid(job), getsid(0));
+    assert_eq!(waitpid_options(job as isize, &mut status, WUNTRACED), job as isize);
+    assert_eq!(status & 0xff, 0x7f);
+    assert_eq!((status >> 8) & 0xff, SIGTSTP as i32);
+    // the whole group is continued
+    assert_eq!(kill(-(job as isize), SIGCONT), 0);
+    assert_eq!(waitpid_options(job as isize, &mut status, WCONTINUED), job as isize);
+    assert_eq!(status, 0xffff);
+    assert_eq!(waitpid(job, &mut status), job as isize);
+    assert_eq!((status >> 8) & 0xff, 7);
+
+    let job = spawn_job(read_console);
+    assert_eq!(waitpid_options(job as isize, &mut status, WUNTRACED), job as isize);
+    assert_eq!((status >> 8) & 0xff, SIGTTIN as i32);
+    if tcgetpgrp() == pgid as isize {
+        assert_eq!(tcsetpgrp(job), 0);
+        assert_eq!(tcgetpgrp(), job as isize);
+        // now in the background, so taking the console back would stop us without this
+        let ignore = SigAction {
+            handler: SIG_IGN,
+            ..Default::default()
+        };
+        assert_eq!(sigaction(SIGTTOU, Some(&ignore), None), 0);
+        assert_eq!(tcsetpgrp(pgid), 0);
+        assert_eq!(tcgetpgrp(), pgid as isize);
+    }
+    assert_eq!(kill(job as isize, SIGKILL), 0);
+    assert_eq!(waitpid(job, &mut status), job as isize);
+    assert_eq!(status & 0x7f, SIGKILL as i32);
+
+    let pid = fork();
+    if pid == 0 {
+        exit(new_session());
+    }
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 0);
+
+    // what ^C, ^\ and ^Z send while the group of initproc is in the foreground, as it is at first
+    for signo in [SIGINT, SIGQUIT, SIGTSTP] {
+        assert_eq!(kill(1, signo), 0);
+    }
+    assert!(initproc_reaps());
+
+    println!("job_control_test passed!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+//! Pages populated on first touch: a bss far larger than what's used, data from the elf, and a
+//! forked child seeing what its parent has touched.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{addr_of, addr_of_mut};
+
+use user_lib::{exit, fork, waitpid};
+
+/// Far more than the memory of the machine, only a few pages are ever touched.
+const BIG_LEN: usize = 256 << 20;
+const PAGE_SIZE: usize = 4096;
+
+static mut BIG: [u8; BIG_LEN] = [0; BIG_LEN];
+static mut DATA: [usize; 4] = [1, 2, 3, 4];
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let big = unsafe { &mut *addr_of_mut!(BIG) };
+    for idx in [0, BIG_LEN / 2, BIG_LEN - 1] {
+        assert_eq!(big[idx], 0);
+        big[idx] = 0x5a;
+    }
+    assert_eq!(unsafe { *addr_of!(DATA) }, [1, 2, 3, 4]);
+    unsafe { (*addr_of_mut!(DATA))[0] = 5 };
+
+    let pid = fork();
+    if pid == 0 {
+        let big = unsafe { &mut *addr_of_mut!(BIG) };
+        // touched by the parent before, and never touched
+        assert_eq!(big[BIG_LEN / 2], 0x5a);
+        assert_eq!(big[BIG_LEN / 2 + PAGE_SIZE], 0);
+        big[BIG_LEN / 2] = 0;
+        assert_eq!(unsafe { *addr_of!(DATA) }, [5, 2, 3, 4]);
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 0);
+    assert_eq!(big[BIG_LEN / 2], 0x5a);
+
+    println!("lazy_test passed!");
+    0
+}
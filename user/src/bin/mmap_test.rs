@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+//! mmap, munmap and mprotect of anonymous memory, private and shared across fork.
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    MAP_ANONYMOUS, MAP_FIXED, MAP_FIXED_NOREPLACE, MAP_PRIVATE, MAP_SHARED, MAP_SHARED_VALIDATE, PROT_NONE, PROT_READ,
+    PROT_WRITE, RLIM_INFINITY, RLIMIT_AS, RLimit, SIGSEGV, mmap, mprotect, munmap, setrlimit,
+    testing::{in_child, load, store},
+};
+
+const PAGE_SIZE: usize = 4096;
+const RW: usize = PROT_READ | PROT_WRITE;
+const EBADF: isize = -9;
+const ENOMEM: isize = -12;
+const EEXIST: isize = -17;
+const EOPNOTSUPP: isize = -95;
+
+fn map(addr: usize, len: usize, prot: usize, flags: usize) -> usize {
+    let ret = mmap(addr, len, prot, flags | MAP_ANONYMOUS, usize::MAX, 0);
+    assert!(ret > 0, "mmap failed with {}", ret);
+    ret as usize
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let area = map(0, 4 * PAGE_SIZE, RW, MAP_PRIVATE);
+    assert_eq!(area % PAGE_SIZE, 0);
+    for page in 0..4 {
+        assert_eq!(load(area + page * PAGE_SIZE), 0);
+        store(area + page * PAGE_SIZE, page as u8 + 1);
+    }
+    // the child writes its own copy
+    assert_eq!(in_child(|| store(area, 0xff)), 0);
+    assert_eq!(load(area), 1);
+
+    // populated by whichever touches a page first, split or not
+    let shared = map(0, 2 * PAGE_SIZE, RW, MAP_SHARED);
+    assert_eq!(in_child(|| store(shared, 42)), 0);
+    assert_eq!(load(shared), 42);
+    assert_eq!(mprotect(shared + PAGE_SIZE, PAGE_SIZE, RW), 0);
+    assert_eq!(in_child(|| store(shared + PAGE_SIZE, 43)), 0);
+    assert_eq!(load(shared + PAGE_SIZE), 43);
+
+    // a hole in the middle, the pages around it stay
+    assert_eq!(munmap(area + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(
+        in_child(|| {
+            load(area + PAGE_SIZE);
+        }) & 0x7f,
+        SIGSEGV as i32
+    );
+    assert_eq!(load(area), 1);
+    assert_eq!(load(area + 2 * PAGE_SIZE), 3);
+    assert_eq!(mprotect(area, 2 * PAGE_SIZE, PROT_READ), ENOMEM);
+
+    let page = area + 2 * PAGE_SIZE;
+    assert_eq!(mprotect(page, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(in_child(|| store(page, 0)) & 0x7f, SIGSEGV as i32);
+    assert_eq!(mprotect(page, PAGE_SIZE, PROT_NONE), 0);
+    assert_eq!(
+        in_child(|| {
+            load(page);
+        }) & 0x7f,
+        SIGSEGV as i32
+    );
+    // the content is kept while it can't be accessed
+    assert_eq!(mprotect(page, PAGE_SIZE, RW), 0);
+    assert_eq!(load(page), 3);
+    store(page, 5);
+    assert_eq!(load(page), 5);
+    assert_eq!(load(area + 3 * PAGE_SIZE), 4);
+
+    assert_eq!(
+        mmap(
+            area,
+            PAGE_SIZE,
+            RW,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+            usize::MAX,
+            0
+        ),
+        EEXIST
+    );
+    assert_eq!(map(area, PAGE_SIZE, RW, MAP_PRIVATE | MAP_FIXED), area);
+    assert_eq!(load(area), 0);
+    // the hole is free for a hint
+    assert_eq!(map(area + PAGE_SIZE, PAGE_SIZE, RW, MAP_PRIVATE), area + PAGE_SIZE);
+
+    // replacing takes no more address space, and what's there stays if the new one doesn't fit
+    let status = in_child(|| {
+        let limit = RLimit {
+            cur: 1 << 30,
+            max: RLIM_INFINITY,
+        };
+        assert_eq!(setrlimit(RLIMIT_AS, &limit), 0);
+        // fill it up to the limit
+        for shift in (PAGE_SIZE.trailing_zeros()..30).rev() {
+            while mmap(0, 1 << shift, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0) > 0 {}
+        }
+        // then free the page after `page`, with one mapped elsewhere in its place under the limit
+        assert_eq!(munmap(page + PAGE_SIZE, PAGE_SIZE), 0);
+        map(0x1000_0000, PAGE_SIZE, PROT_NONE, MAP_PRIVATE);
+        let fixed = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+        assert_eq!(mmap(page, 2 * PAGE_SIZE, RW, fixed, usize::MAX, 0), ENOMEM);
+        assert_eq!(load(page), 5);
+        assert_eq!(map(page, PAGE_SIZE, RW, MAP_PRIVATE | MAP_FIXED), page);
+        assert_eq!(load(page), 0);
+    });
+    assert_eq!(status, 0);
+
+    assert_eq!(mmap(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, 3, 0), EBADF);
+    // a flag no one knows is only rejected when asked to
+    let unknown = 0x4000_0000;
+    let validated = MAP_SHARED_VALIDATE | MAP_ANONYMOUS | unknown;
+    assert_eq!(mmap(0, PAGE_SIZE, RW, validated, usize::MAX, 0), EOPNOTSUPP);
+    let ignored = map(0, PAGE_SIZE, RW, MAP_SHARED | unknown);
+    assert_eq!(munmap(ignored, PAGE_SIZE), 0);
+    // more than there is below the mmap base
+    assert_eq!(mmap(0, 1 << 60, RW, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0), ENOMEM);
+    assert_eq!(munmap(area, 4 * PAGE_SIZE), 0);
+    assert_eq!(munmap(shared, 2 * PAGE_SIZE), 0);
+
+    println!("mmap_test passed!");
+    0
+}
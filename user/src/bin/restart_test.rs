@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+//! Blocking syscalls interrupted by a handler return EINTR, unless the handler has `SA_RESTART`,
+//! which nanosleep ignores like Linux. Without a handler, e.g. stopped and continued, nanosleep
+//! sleeps on for the time left only.
+//! The interrupting thread relies on the main one blocking by the time it wakes up.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use user_lib::{
+    SA_RESTART, SIGCONT, SIGSTOP, SIGUSR1, SigAction, WNOHANG, exit, exit_thread, fork, futex_wait, futex_wake, getpid,
+    gettid, kill, sigaction, sleep, tgkill, thread_create, waitpid_options,
+};
+
+const EINTR: isize = -4;
+
+static HANDLED: AtomicU32 = AtomicU32::new(0);
+/// set once the interrupting thread is done
+static DONE: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn on_usr1(_signo: i32) {
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Send SIGUSR1 to thread `tid` once it's blocked, then set `DONE`.
+extern "C" fn interrupter(tid: usize) -> ! {
+    sleep(50);
+    assert_eq!(tgkill(getpid() as usize, tid, SIGUSR1), 0);
+    sleep(50);
+    DONE.store(1, Ordering::SeqCst);
+    futex_wake(&DONE, 1);
+    exit_thread(0)
+}
+
+/// Start the interrupting thread and return what `blocking` returns.
+fn interrupt(flags: usize, blocking: impl FnOnce() -> isize) -> isize {
+    let act = SigAction {
+        handler: on_usr1 as usize,
+        flags,
+        mask: 0,
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&act), None), 0);
+    DONE.store(0, Ordering::SeqCst);
+    assert!(thread_create(interrupter, gettid() as usize) > 0);
+    let ret = blocking();
+    while DONE.load(Ordering::SeqCst) == 0 {
+        futex_wait(&DONE, 0);
+    }
+    ret
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(interrupt(0, || futex_wait(&DONE, 0)), EINTR);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+
+    // woken up by the thread at last, after the handler has run in between
+    assert_eq!(interrupt(SA_RESTART, || futex_wait(&DONE, 0)), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+
+    assert_eq!(interrupt(SA_RESTART, || sleep(1000)), EINTR);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 3);
+
+    // done at 400 ms, or at 500 ms at least if it slept for 400 ms again after being continued
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sleep(400), 0);
+        exit(0);
+    }
+    sleep(100);
+    assert_eq!(kill(pid, SIGSTOP), 0);
+    assert_eq!(kill(pid, SIGCONT), 0);
+    sleep(350);
+    let mut status = -1;
+    assert_eq!(
+        waitpid_options(pid, &mut status, WNOHANG),
+        pid,
+        "nanosleep restarted with the whole time"
+    );
+    assert_eq!(status, 0);
+
+    println!("restart_test passed!");
+    0
+}
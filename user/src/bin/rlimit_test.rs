@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+//! Resource limits: getting and setting them, and what happens when a process runs into them.
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    RLIM_INFINITY, RLIMIT_AS, RLIMIT_CORE, RLIMIT_CPU, RLIMIT_NPROC, RLimit, SIGQUIT, SIGXCPU, exit, exit_thread, fork,
+    getppid, getrlimit, kill, prlimit, setrlimit, setsid, thread_create, waitpid, yield_,
+};
+
+const EPERM: isize = -1;
+const EAGAIN: isize = -11;
+const ENOMEM: isize = -12;
+const EINVAL: isize = -22;
+
+extern "C" fn never_run(_arg: usize) -> ! {
+    exit_thread(0)
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut nproc = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NPROC, &mut nproc), 0);
+    assert!(nproc.cur <= nproc.max);
+    // the soft limit may go down and back up, but not over the hard one
+    let lowered = RLimit { cur: 0, max: nproc.max };
+    assert_eq!(setrlimit(RLIMIT_NPROC, &lowered), 0);
+    assert_eq!(fork(), EAGAIN);
+    assert_eq!(thread_create(never_run, 0), EAGAIN);
+    assert_eq!(setrlimit(RLIMIT_NPROC, &nproc), 0);
+    let raised = RLimit {
+        cur: nproc.max,
+        max: nproc.max + 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_NPROC, &raised), EPERM);
+    let inverted = RLimit { cur: 2, max: 1 };
+    assert_eq!(setrlimit(RLIMIT_NPROC, &inverted), EINVAL);
+
+    // not even the stack of a new thread fits
+    let mut address_space = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_AS, &mut address_space), 0);
+    assert_eq!(address_space.max, RLIM_INFINITY);
+    let tiny = RLimit {
+        cur: 0,
+        max: RLIM_INFINITY,
+    };
+    assert_eq!(setrlimit(RLIMIT_AS, &tiny), 0);
+    assert_eq!(thread_create(never_run, 0), ENOMEM);
+    assert_eq!(setrlimit(RLIMIT_AS, &address_space), 0);
+
+    // SIGXCPU after a second of running
+    let pid = fork();
+    if pid == 0 {
+        let cpu = RLimit { cur: 1, max: 5 };
+        assert_eq!(setrlimit(RLIMIT_CPU, &cpu), 0);
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status & 0x7f, SIGXCPU as i32);
+
+    // inherited by the child, and set by the parent for it
+    let pid = fork();
+    if pid == 0 {
+        let mut inherited = RLimit::default();
+        assert_eq!(getrlimit(RLIMIT_NPROC, &mut inherited), 0);
+        if inherited != nproc {
+            exit(1);
+        }
+        loop {
+            yield_();
+        }
+    }
+    let mut core = RLimit::default();
+    assert_eq!(prlimit(pid as usize, RLIMIT_CORE, None, Some(&mut core)), 0);
+    assert_eq!(core.cur, 0);
+    let dump = RLimit {
+        cur: RLIM_INFINITY,
+        max: RLIM_INFINITY,
+    };
+    assert_eq!(prlimit(pid as usize, RLIMIT_CORE, Some(&dump), None), 0);
+    assert_eq!(kill(pid, SIGQUIT), 0);
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status & 0xff, 0x80 | SIGQUIT as i32);
+    // but not by anyone else for the parent
+    assert_eq!(prlimit(getppid() as usize, RLIMIT_CORE, None, Some(&mut core)), EPERM);
+
+    // all the threads count, so a new session with the child alone doesn't get around it
+    let pid = fork();
+    if pid == 0 {
+        assert!(setsid() > 0);
+        let two = RLimit { cur: 2, max: nproc.max };
+        assert_eq!(setrlimit(RLIMIT_NPROC, &two), 0);
+        assert_eq!(fork(), EAGAIN);
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 0);
+
+    println!("rlimit_test passed!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+//! Real-time policies and affinity. How a PI mutex lends priorities is left to futex_pi_test.
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    SCHED_FIFO, SCHED_OTHER, SCHED_RR, nice, sched_get_priority_max, sched_get_priority_min, sched_getaffinity,
+    sched_getparam, sched_getscheduler, sched_setaffinity, sched_setscheduler, yield_,
+};
+
+const EINVAL: isize = -22;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(sched_get_priority_min(SCHED_FIFO), 1);
+    assert_eq!(sched_get_priority_max(SCHED_FIFO), 99);
+    assert_eq!(sched_get_priority_min(SCHED_RR), 1);
+    assert_eq!(sched_get_priority_max(SCHED_RR), 99);
+    assert_eq!(sched_get_priority_max(SCHED_OTHER), 0);
+    assert_eq!(sched_get_priority_max(4), EINVAL);
+
+    // priorities out of the range of the policy
+    assert_eq!(sched_setscheduler(0, SCHED_FIFO, 0), EINVAL);
+    assert_eq!(sched_setscheduler(0, SCHED_RR, 100), EINVAL);
+    assert_eq!(sched_setscheduler(0, SCHED_OTHER, 1), EINVAL);
+    assert_eq!(sched_getscheduler(0), SCHED_OTHER as isize);
+
+    assert_eq!(sched_setscheduler(0, SCHED_FIFO, 10), 0);
+    assert_eq!(sched_getscheduler(0), SCHED_FIFO as isize);
+    assert_eq!(sched_getparam(0), 10);
+    // a fifo thread keeps the hart when nothing more favorable is ready
+    assert_eq!(yield_(), 0);
+
+    assert_eq!(sched_setscheduler(0, SCHED_RR, 99), 0);
+    assert_eq!(sched_getscheduler(0), SCHED_RR as isize);
+    assert_eq!(sched_getparam(0), 99);
+
+    // nice is kept aside while running as real-time
+    assert_eq!(nice(5), 5);
+    assert_eq!(sched_setscheduler(0, SCHED_OTHER, 0), 0);
+    assert_eq!(sched_getparam(0), 0);
+    assert_eq!(nice(-5), 0);
+
+    // pinned to one hart and back
+    let harts = sched_getaffinity(0);
+    assert!(harts > 0);
+    let hart = harts & -harts;
+    assert_eq!(sched_setaffinity(0, 0), EINVAL);
+    assert_eq!(sched_setaffinity(0, hart as usize), 0);
+    assert_eq!(sched_getaffinity(0), hart);
+    assert_eq!(sched_setaffinity(0, harts as usize), 0);
+
+    println!("sched_rt_test passed!");
+    0
+}
@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+//! Handlers, masks and default actions of signals, raised by kill and by faults.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use user_lib::{
+    SA_SIGINFO, SIG_BLOCK, SIG_UNBLOCK, SIGCHLD, SIGKILL, SIGSEGV, SIGSTOP, SIGTERM, SIGUSR1, SIGUSR2, SigAction, fork,
+    getpid, kill, sigaction, sigmask, sigpending, sigprocmask, testing::initproc_reaps, waitpid, yield_,
+};
+
+static USR1_COUNT: AtomicU32 = AtomicU32::new(0);
+/// si_pid of the last SIGUSR2
+static USR2_SENDER: AtomicUsize = AtomicUsize::new(0);
+static CHLD_COUNT: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn on_usr1(signo: i32) {
+    assert_eq!(signo as usize, SIGUSR1);
+    USR1_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn on_usr2(signo: i32, info: *const u8, _ucontext: *const u8) {
+    // si_signo at 0 and si_pid at 16 of siginfo_t
+    let (si_signo, si_pid) = unsafe { (*(info as *const i32), *(info.add(16) as *const i32)) };
+    assert_eq!(si_signo, signo);
+    USR2_SENDER.store(si_pid as usize, Ordering::SeqCst);
+}
+
+extern "C" fn on_chld(_signo: i32) {
+    CHLD_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+fn handle(signo: usize, handler: usize, flags: usize) {
+    let act = SigAction {
+        handler,
+        flags,
+        mask: 0,
+    };
+    assert_eq!(sigaction(signo, Some(&act), None), 0);
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let pid = getpid();
+
+    // taken on the way back from kill
+    handle(SIGUSR1, on_usr1 as usize, 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 1);
+
+    // held while blocked, and taken once however many times it's sent
+    let set = sigmask(SIGUSR1);
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&set), None), 0);
+    kill(pid, SIGUSR1);
+    kill(pid, SIGUSR1);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 1);
+    assert_eq!(sigpending(), set);
+    assert_eq!(sigprocmask(SIG_UNBLOCK, Some(&set), None), 0);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 2);
+    assert_eq!(sigpending(), 0);
+
+    handle(SIGUSR2, on_usr2 as usize, SA_SIGINFO);
+    kill(pid, SIGUSR2);
+    assert_eq!(USR2_SENDER.load(Ordering::SeqCst), pid as usize);
+
+    // SIGKILL can't be caught
+    assert!(sigaction(SIGKILL, Some(&SigAction::default()), None) < 0);
+
+    handle(SIGCHLD, on_chld as usize, 0);
+    let child = fork();
+    if child == 0 {
+        // killed by SIGSEGV
+        unsafe { core::ptr::null_mut::<u32>().write_volatile(0) };
+        unreachable!();
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(child as usize, &mut status), child);
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+    assert_eq!(CHLD_COUNT.load(Ordering::SeqCst), 1);
+
+    // as well as at an address that isn't even canonical
+    let child = fork();
+    if child == 0 {
+        unsafe { ((1usize << 63) as *mut u32).write_volatile(0) };
+        unreachable!();
+    }
+    assert_eq!(waitpid(child as usize, &mut status), child);
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(child, SIGKILL), 0);
+    assert_eq!(waitpid(child as usize, &mut status), child);
+    assert_eq!(status & 0x7f, SIGKILL as i32);
+
+    // initproc takes no signal it has no handler for, not even SIGKILL
+    for signo in [SIGKILL, SIGTERM, SIGSTOP] {
+        assert_eq!(kill(1, signo), 0);
+    }
+    assert!(initproc_reaps());
+
+    println!("sig_test passed!");
+    0
+}
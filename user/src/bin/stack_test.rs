@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+//! The stack of the main thread growing on demand: deep recursion, the kernel writing below it,
+//! and where it stops, at `RLIMIT_STACK`, far below the sp and the guard gap above another
+//! mapping.
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+use user_lib::{
+    MAP_ANONYMOUS, MAP_FIXED_NOREPLACE, MAP_PRIVATE, PROT_READ, PROT_WRITE, RLIM_INFINITY, RLIMIT_NOFILE, RLIMIT_STACK,
+    RLimit, SIGSEGV, getrlimit, mmap, setrlimit,
+    testing::{in_child, store},
+};
+
+const PAGE_SIZE: usize = 4096;
+const FRAME_SIZE: usize = 4096;
+
+/// Recurse with a frame of at least `FRAME_SIZE` bytes each time.
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; FRAME_SIZE];
+    frame[0] = depth as u8;
+    black_box(&mut frame);
+    match depth {
+        0 => 0,
+        depth => recurse(depth - 1) + black_box(&frame)[0] as usize,
+    }
+}
+
+/// Recurse like `recurse` and call `f` with an address in the deepest frame.
+fn at_depth(depth: usize, f: &dyn Fn(usize)) {
+    let mut frame = [0u8; FRAME_SIZE];
+    black_box(&mut frame);
+    match depth {
+        0 => f(frame.as_ptr() as usize),
+        depth => at_depth(depth - 1, f),
+    }
+    black_box(&frame);
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // far more than the 32 KiB the stack starts with
+    let depth = 256;
+    assert_eq!(recurse(depth), (1..=depth).map(|n| n as u8 as usize).sum());
+
+    // over the limit
+    let status = in_child(|| {
+        let small = RLimit {
+            cur: 256 << 10,
+            max: RLIM_INFINITY,
+        };
+        assert_eq!(setrlimit(RLIMIT_STACK, &small), 0);
+        recurse(1024);
+    });
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+
+    // not far below the sp, even within the limit
+    let here = &depth as *const usize as usize;
+    let status = in_child(|| store(here - (3 << 20), 1));
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+
+    // not into the gap above a mapping, even near the sp
+    let status = in_child(|| {
+        at_depth(512, &|deepest| {
+            let below = (deepest - (32 << 10)) & !(PAGE_SIZE - 1);
+            let mapped = below - (512 << 10);
+            let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE;
+            assert_eq!(
+                mmap(mapped, PAGE_SIZE, PROT_READ | PROT_WRITE, flags, usize::MAX, 0),
+                mapped as isize
+            );
+            store(below, 1);
+        })
+    });
+    assert_eq!(status & 0x7f, SIGSEGV as i32);
+
+    // the kernel grows it as well, below the deepest frame so far
+    at_depth(512, &|deepest| {
+        let below = (deepest - (32 << 10)) & !(PAGE_SIZE - 1);
+        let mut nofile = RLimit::default();
+        assert_eq!(getrlimit(RLIMIT_NOFILE, &mut nofile), 0);
+        assert_eq!(getrlimit(RLIMIT_NOFILE, unsafe { &mut *(below as *mut RLimit) }), 0);
+        assert_eq!(unsafe { (below as *const RLimit).read_volatile() }, nofile);
+    });
+
+    println!("stack_test passed!");
+    0
+}
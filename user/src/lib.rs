@@ -4,26 +4,40 @@
 extern crate alloc;
 
 use alloc::vec::Vec;
-use core::ptr::addr_of_mut;
+use core::{
+    alloc::Layout,
+    ptr::{null, null_mut},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-use buddy_system_allocator::LockedHeap;
+use buddy_system_allocator::{Heap, LockedHeapWithRescue};
 
-mod console;
+pub mod console;
 mod panic;
 mod syscall;
+pub mod testing;
 
+/// The heap is empty at first, and grows by moving the program break whenever it runs out.
 #[global_allocator]
-static HEAP: LockedHeap<16> = LockedHeap::empty();
+static HEAP: LockedHeapWithRescue<32> = LockedHeapWithRescue::new(grow_heap);
 
-const USER_HEAP_SIZE: usize = 32768;
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+/// What the heap grows by at least.
+const HEAP_GROWTH: usize = 0x1_0000;
+
+/// Add memory to the heap for `layout`. A range twice the size of the block it needs has an
+/// aligned block in it wherever it starts. Allocating fails as usual if the break can't be moved.
+fn grow_heap(heap: &mut Heap<32>, layout: &Layout) {
+    let block = layout.size().next_power_of_two().max(layout.align());
+    let len = (block * 2).max(HEAP_GROWTH);
+    let start = syscall::sys_brk(0) as usize;
+    if syscall::sys_brk(start + len) as usize == start + len {
+        unsafe { heap.add_to_heap(start, start + len) };
+    }
+}
 
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
-    unsafe {
-        HEAP.lock().init(addr_of_mut!(HEAP_SPACE) as usize, USER_HEAP_SIZE);
-    }
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
         let str_start = unsafe { ((argv + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
@@ -41,10 +55,215 @@ fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main!");
 }
 
+/// Exit the whole process like `exit(3)`.
 pub fn exit(exit_code: i32) -> ! {
+    syscall::sys_exit_group(exit_code);
+}
+
+/// Exit the calling thread only.
+pub fn exit_thread(exit_code: i32) -> ! {
     syscall::sys_exit(exit_code);
 }
 
+/// CLONE_VM | CLONE_FS | CLONE_FILES | CLONE_SIGHAND | CLONE_THREAD | CLONE_SYSVSEM
+const CLONE_THREAD_FLAGS: usize = 0x100 | 0x200 | 0x400 | 0x800 | 0x10000 | 0x40000;
+
+/// Start a thread running `entry(arg)`, which has to end with `exit_thread`. Return its tid.
+pub fn thread_create(entry: extern "C" fn(usize) -> !, arg: usize) -> isize {
+    syscall::sys_clone_thread(CLONE_THREAD_FLAGS, entry, arg)
+}
+
+/// Sleep for `ms` milliseconds.
+pub fn sleep(ms: usize) -> isize {
+    let req = [ms / 1000, ms % 1000 * 1_000_000];
+    syscall::sys_nanosleep(&req, null_mut())
+}
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_LOCK_PI: usize = 6;
+const FUTEX_UNLOCK_PI: usize = 7;
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// Block while `futex` is `val`, until `futex_wake` on it.
+pub fn futex_wait(futex: &AtomicU32, val: u32) -> isize {
+    syscall::sys_futex(futex.as_ptr(), FUTEX_WAIT | FUTEX_PRIVATE_FLAG, val, 0, null(), 0)
+}
+
+/// Wake up at most `count` threads blocked on `futex`, return how many are woken up.
+pub fn futex_wake(futex: &AtomicU32, count: u32) -> isize {
+    syscall::sys_futex(futex.as_ptr(), FUTEX_WAKE | FUTEX_PRIVATE_FLAG, count, 0, null(), 0)
+}
+
+/// A mutex with priority inheritance like a pthread mutex with `PTHREAD_PRIO_INHERIT`.
+/// The word is 0 when it's free and the tid of the owner otherwise, so the kernel is only asked
+/// when there's contention.
+pub struct PiMutex(AtomicU32);
+
+impl PiMutex {
+    pub const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    pub fn lock(&self) {
+        let tid = gettid() as u32;
+        if self
+            .0
+            .compare_exchange(0, tid, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            let ret = syscall::sys_futex(self.0.as_ptr(), FUTEX_LOCK_PI | FUTEX_PRIVATE_FLAG, 0, 0, null(), 0);
+            assert_eq!(ret, 0, "FUTEX_LOCK_PI failed");
+        }
+    }
+
+    pub fn unlock(&self) {
+        let tid = gettid() as u32;
+        // there are waiters to hand it over to if it's not just the tid
+        if self
+            .0
+            .compare_exchange(tid, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            let ret = syscall::sys_futex(self.0.as_ptr(), FUTEX_UNLOCK_PI | FUTEX_PRIVATE_FLAG, 0, 0, null(), 0);
+            assert_eq!(ret, 0, "FUTEX_UNLOCK_PI failed");
+        }
+    }
+}
+
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGXCPU: usize = 24;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+pub const SA_SIGINFO: usize = 4;
+pub const SA_RESTART: usize = 0x1000_0000;
+pub const SA_NODEFER: usize = 0x4000_0000;
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// `struct sigaction` of the kernel, handlers return through a trampoline of the kernel so
+/// there's no restorer.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SigAction {
+    /// `SIG_DFL`, `SIG_IGN` or a `extern "C" fn(i32)`, which takes `(i32, *const u8, *const u8)`
+    /// with `SA_SIGINFO`
+    pub handler: usize,
+    pub flags: usize,
+    pub mask: u64,
+}
+
+/// The bit of `signo` in a signal set.
+pub const fn sigmask(signo: usize) -> u64 {
+    1 << (signo - 1)
+}
+
+pub fn sigaction(signo: usize, act: Option<&SigAction>, oldact: Option<&mut SigAction>) -> isize {
+    syscall::sys_rt_sigaction(
+        signo,
+        act.map_or(null(), |act| act as *const _),
+        oldact.map_or(null_mut(), |oldact| oldact as *mut _),
+    )
+}
+
+pub fn sigprocmask(how: usize, set: Option<&u64>, oldset: Option<&mut u64>) -> isize {
+    syscall::sys_rt_sigprocmask(
+        how,
+        set.map_or(null(), |set| set as *const _),
+        oldset.map_or(null_mut(), |oldset| oldset as *mut _),
+    )
+}
+
+/// Return the signals pending but blocked.
+pub fn sigpending() -> u64 {
+    let mut set = 0;
+    syscall::sys_rt_sigpending(&mut set as *mut u64);
+    set
+}
+
+pub fn kill(pid: isize, signo: usize) -> isize {
+    syscall::sys_kill(pid, signo)
+}
+
+/// Send `signo` to thread `tid` of process `tgid`.
+pub fn tgkill(tgid: usize, tid: usize, signo: usize) -> isize {
+    syscall::sys_tgkill(tgid, tid, signo)
+}
+
+pub fn yield_() -> isize {
+    syscall::sys_yield()
+}
+
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_FIFO: usize = 1;
+pub const SCHED_RR: usize = 2;
+
+/// Set the policy of process `pid`(0 for the caller) with `sched_priority` of `priority`.
+pub fn sched_setscheduler(pid: usize, policy: usize, priority: i32) -> isize {
+    syscall::sys_sched_setscheduler(pid, policy, &priority as *const i32)
+}
+
+pub fn sched_getscheduler(pid: usize) -> isize {
+    syscall::sys_sched_getscheduler(pid)
+}
+
+/// Return `sched_priority` of process `pid`(0 for the caller).
+pub fn sched_getparam(pid: usize) -> isize {
+    let mut priority = 0i32;
+    match syscall::sys_sched_getparam(pid, &mut priority as *mut i32) {
+        0 => priority as isize,
+        err => err,
+    }
+}
+
+/// Let process `pid`(0 for the caller) run only on the harts in `mask`, a bit for each hart id.
+pub fn sched_setaffinity(pid: usize, mask: usize) -> isize {
+    syscall::sys_sched_setaffinity(pid, core::mem::size_of::<usize>(), &mask as *const usize)
+}
+
+/// Return the harts process `pid`(0 for the caller) may run on, a bit for each hart id.
+pub fn sched_getaffinity(pid: usize) -> isize {
+    let mut mask = 0usize;
+    match syscall::sys_sched_getaffinity(pid, core::mem::size_of::<usize>(), &mut mask as *mut usize) {
+        err if err < 0 => err,
+        _ => mask as isize,
+    }
+}
+
+pub fn sched_get_priority_max(policy: usize) -> isize {
+    syscall::sys_sched_get_priority_max(policy)
+}
+
+pub fn sched_get_priority_min(policy: usize) -> isize {
+    syscall::sys_sched_get_priority_min(policy)
+}
+
+/// Add `inc` to the nice value of the calling thread, return the new nice value.
+pub fn nice(inc: i32) -> isize {
+    let nice = 20 - syscall::sys_getpriority(0, 0);
+    match syscall::sys_setpriority(0, 0, nice as i32 + inc) {
+        0 => 20 - syscall::sys_getpriority(0, 0),
+        err => err,
+    }
+}
+
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     syscall::sys_read(fd, buf)
 }
@@ -53,14 +272,141 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
     syscall::sys_write(fd, buf)
 }
 
+pub fn getpid() -> isize {
+    syscall::sys_getpid()
+}
+
+pub fn getppid() -> isize {
+    syscall::sys_getppid()
+}
+
+pub fn gettid() -> isize {
+    syscall::sys_gettid()
+}
+
 pub fn fork() -> isize {
     syscall::sys_fork()
 }
 
+/// Fork and block until the child calls `exec` or exits.
+pub fn vfork() -> isize {
+    syscall::sys_vfork()
+}
+
 pub fn wait(exit_code: &mut i32) -> isize {
     syscall::sys_waitpid(-1, exit_code as *mut _)
 }
 
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    syscall::sys_waitpid(pid as isize, exit_code as *mut _)
+}
+
+pub const WNOHANG: usize = 1;
+pub const WUNTRACED: usize = 2;
+pub const WCONTINUED: usize = 8;
+
+/// `waitpid(3)` with `options`, `pid` is -1 for any child, 0 for any child in the same group
+/// and `-pgid` for any child in group `pgid`.
+pub fn waitpid_options(pid: isize, wstatus: &mut i32, options: usize) -> isize {
+    syscall::sys_wait4(pid, wstatus as *mut _, options)
+}
+
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    syscall::sys_setpgid(pid, pgid)
+}
+
+pub fn getpgid(pid: usize) -> isize {
+    syscall::sys_getpgid(pid)
+}
+
+pub fn setsid() -> isize {
+    syscall::sys_setsid()
+}
+
+pub fn getsid(pid: usize) -> isize {
+    syscall::sys_getsid(pid)
+}
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// The foreground group of the console.
+pub fn tcgetpgrp() -> isize {
+    let mut pgid = 0i32;
+    match syscall::sys_ioctl(0, TIOCGPGRP, &mut pgid as *mut i32 as usize) {
+        0 => pgid as isize,
+        err => err,
+    }
+}
+
+/// Make group `pgid` the foreground group of the console.
+pub fn tcsetpgrp(pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    syscall::sys_ioctl(0, TIOCSPGRP, &pgid as *const i32 as usize)
+}
+
+/// Move the program break to `addr` and return the new one, which is the current one on failure.
+pub fn brk(addr: usize) -> usize {
+    syscall::sys_brk(addr) as usize
+}
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_SHARED_VALIDATE: usize = 0x03;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+pub const MAP_FIXED_NOREPLACE: usize = 0x10_0000;
+
+/// Map memory and return its address, or a negative errno.
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    syscall::sys_mmap(addr, len, prot, flags, fd, offset)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    syscall::sys_munmap(addr, len)
+}
+
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall::sys_mprotect(addr, len, prot)
+}
+
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    syscall::sys_getrlimit(resource, rlim)
+}
+
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    syscall::sys_setrlimit(resource, rlim)
+}
+
+/// Set the limit of `resource` of process `pid` if `new_limit` is given, and get the old one.
+pub fn prlimit(pid: usize, resource: usize, new_limit: Option<&RLimit>, old_limit: Option<&mut RLimit>) -> isize {
+    syscall::sys_prlimit64(
+        pid,
+        resource,
+        new_limit.map_or(null(), |limit| limit as *const _),
+        old_limit.map_or(null_mut(), |limit| limit as *mut _),
+    )
+}
+
 pub fn exec(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
     syscall::sys_exec(path, args, envp)
 }
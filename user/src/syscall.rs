@@ -1,11 +1,46 @@
 use core::arch::asm;
 
+use crate::{RLimit, SigAction};
+
+const SYSCALL_IOCTL: usize = 29;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
+const SYSCALL_SCHED_GETSCHEDULER: usize = 120;
+const SYSCALL_SCHED_GETPARAM: usize = 121;
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
+const SYSCALL_SCHED_YIELD: usize = 124;
+const SYSCALL_SCHED_GET_PRIORITY_MAX: usize = 125;
+const SYSCALL_SCHED_GET_PRIORITY_MIN: usize = 126;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_TGKILL: usize = 131;
+const SYSCALL_RT_SIGACTION: usize = 134;
+const SYSCALL_RT_SIGPROCMASK: usize = 135;
+const SYSCALL_RT_SIGPENDING: usize = 136;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETPPID: usize = 173;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_CLONE: usize = 220;
 const SYSCALL_EXECVE: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_PRLIMIT64: usize = 261;
 
 fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
@@ -43,6 +78,51 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("sys_exit never returns!");
 }
 
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT_GROUP, [exit_code as usize, 0, 0]);
+    panic!("sys_exit_group never returns!");
+}
+
+pub fn sys_yield() -> isize {
+    syscall(SYSCALL_SCHED_YIELD, [0, 0, 0])
+}
+
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: *const i32) -> isize {
+    syscall(SYSCALL_SCHED_SETSCHEDULER, [pid, policy, param as usize])
+}
+
+pub fn sys_sched_getscheduler(pid: usize) -> isize {
+    syscall(SYSCALL_SCHED_GETSCHEDULER, [pid, 0, 0])
+}
+
+pub fn sys_sched_getparam(pid: usize, param: *mut i32) -> isize {
+    syscall(SYSCALL_SCHED_GETPARAM, [pid, param as usize, 0])
+}
+
+pub fn sys_sched_setaffinity(pid: usize, len: usize, mask: *const usize) -> isize {
+    syscall(SYSCALL_SCHED_SETAFFINITY, [pid, len, mask as usize])
+}
+
+pub fn sys_sched_getaffinity(pid: usize, len: usize, mask: *mut usize) -> isize {
+    syscall(SYSCALL_SCHED_GETAFFINITY, [pid, len, mask as usize])
+}
+
+pub fn sys_sched_get_priority_max(policy: usize) -> isize {
+    syscall(SYSCALL_SCHED_GET_PRIORITY_MAX, [policy, 0, 0])
+}
+
+pub fn sys_sched_get_priority_min(policy: usize) -> isize {
+    syscall(SYSCALL_SCHED_GET_PRIORITY_MIN, [policy, 0, 0])
+}
+
+pub fn sys_setpriority(which: usize, who: usize, prio: i32) -> isize {
+    syscall(SYSCALL_SETPRIORITY, [which, who, prio as usize])
+}
+
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    syscall(SYSCALL_GETPRIORITY, [which, who, 0])
+}
+
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(SYSCALL_READ, [fd, buffer.as_mut_ptr() as usize, buffer.len()])
 }
@@ -51,14 +131,151 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_getpid() -> isize {
+    syscall(SYSCALL_GETPID, [0, 0, 0])
+}
+
+pub fn sys_getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0, 0, 0])
+}
+
+pub fn sys_gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0, 0, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall6(SYSCALL_CLONE, [0, 0, 0, 0, 0, 0])
 }
 
+/// CLONE_VM | CLONE_VFORK | SIGCHLD, like `vfork(2)`
+pub fn sys_vfork() -> isize {
+    syscall6(SYSCALL_CLONE, [0x100 | 0x4000 | 17, 0, 0, 0, 0, 0])
+}
+
+/// Create a thread sharing everything with the caller, which runs `entry(arg)` on a stack mapped
+/// by the kernel. Return its tid.
+pub fn sys_clone_thread(flags: usize, entry: extern "C" fn(usize) -> !, arg: usize) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            // the new thread returns 0 on a stack without any frame of ours
+            "bnez a0, 1f",
+            "mv a0, {arg}",
+            "jr {entry}",
+            "1:",
+            entry = in(reg) entry,
+            arg = in(reg) arg,
+            inlateout("x10") flags => ret,
+            in("x11") 0,
+            in("x12") 0,
+            in("x13") 0,
+            in("x14") 0,
+            in("x17") SYSCALL_CLONE
+        );
+    }
+    ret
+}
+
+pub fn sys_futex(uaddr: *const u32, op: usize, val: u32, timeout: usize, uaddr2: *const u32, val3: u32) -> isize {
+    syscall6(SYSCALL_FUTEX, [
+        uaddr as usize,
+        op,
+        val as usize,
+        timeout,
+        uaddr2 as usize,
+        val3 as usize,
+    ])
+}
+
+pub fn sys_nanosleep(req: *const [usize; 2], rem: *mut [usize; 2]) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [req as usize, rem as usize, 0])
+}
+
+pub fn sys_kill(pid: isize, signo: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signo, 0])
+}
+
+pub fn sys_tgkill(tgid: usize, tid: usize, signo: usize) -> isize {
+    syscall(SYSCALL_TGKILL, [tgid, tid, signo])
+}
+
+/// `sigsetsize` is always 8, the size of the kernel `sigset_t`.
+pub fn sys_rt_sigaction(signo: usize, act: *const SigAction, oldact: *mut SigAction) -> isize {
+    syscall6(SYSCALL_RT_SIGACTION, [signo, act as usize, oldact as usize, 8, 0, 0])
+}
+
+pub fn sys_rt_sigprocmask(how: usize, set: *const u64, oldset: *mut u64) -> isize {
+    syscall6(SYSCALL_RT_SIGPROCMASK, [how, set as usize, oldset as usize, 8, 0, 0])
+}
+
+pub fn sys_rt_sigpending(set: *mut u64) -> isize {
+    syscall(SYSCALL_RT_SIGPENDING, [set as usize, 8, 0])
+}
+
 pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
     syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
 }
 
+pub fn sys_wait4(pid: isize, wstatus: *mut i32, options: usize) -> isize {
+    syscall6(SYSCALL_WAITPID, [pid as usize, wstatus as usize, options, 0, 0, 0])
+}
+
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, request, arg])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0, 0, 0])
+}
+
+pub fn sys_getsid(pid: usize) -> isize {
+    syscall(SYSCALL_GETSID, [pid, 0, 0])
+}
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    syscall6(SYSCALL_MMAP, [addr, len, prot, flags, fd, offset])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot])
+}
+
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as usize, 0])
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as usize, 0])
+}
+
+pub fn sys_prlimit64(pid: usize, resource: usize, new_limit: *const RLimit, old_limit: *mut RLimit) -> isize {
+    syscall6(SYSCALL_PRLIMIT64, [
+        pid,
+        resource,
+        new_limit as usize,
+        old_limit as usize,
+        0,
+        0,
+    ])
+}
+
 pub fn sys_exec(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
     syscall(SYSCALL_EXECVE, [
         path.as_ptr() as usize,
@@ -0,0 +1,57 @@
+//! Helpers shared by the test programs.
+
+use crate::{MAP_ANONYMOUS, MAP_SHARED, PROT_READ, PROT_WRITE, exit, fork, kill, mmap, munmap, sleep, waitpid};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Load a byte from `addr`, which may fault.
+pub fn load(addr: usize) -> u8 {
+    unsafe { (addr as *const u8).read_volatile() }
+}
+
+/// Store a byte at `addr`, which may fault.
+pub fn store(addr: usize, value: u8) {
+    unsafe { (addr as *mut u8).write_volatile(value) }
+}
+
+/// Run `f` in a child and return its wait status.
+pub fn in_child(f: impl FnOnce()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    status
+}
+
+/// Whether initproc still reaps orphans, so it's neither gone nor stopped.
+pub fn initproc_reaps() -> bool {
+    // the child passes the pid of its orphan through a shared page
+    let page = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(page > 0, "mmap failed with {}", page);
+    let shared = page as *mut isize;
+    let status = in_child(|| {
+        let orphan = fork();
+        if orphan == 0 {
+            exit(0);
+        }
+        unsafe { shared.write_volatile(orphan) };
+    });
+    assert_eq!(status, 0);
+    let orphan = unsafe { shared.read_volatile() };
+    assert_eq!(munmap(page as usize, PAGE_SIZE), 0);
+    // it can't be sent signals once it's reaped
+    (0..100).any(|_| {
+        sleep(10);
+        kill(orphan, 0) < 0
+    })
+}